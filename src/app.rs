@@ -1,12 +1,20 @@
+use crate::error_handling;
 use crate::screens;
 use crate::traits::{EventHandler, FrameRenderer, ScreenMessage};
 use crossterm::event::{self, Event, KeyCode};
 use ratatui::Frame;
-use std::io;
+use std::time::{Duration, Instant};
+
+// How long to wait for input before redrawing anyway, so the game clock keeps ticking.
+const TICK_RATE: Duration = Duration::from_secs(1);
+
+// How often a game in progress is saved, on top of saving when the app exits.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 pub struct App {
     pub state: GlobalState,
     pub exit: bool,
+    last_autosave: Instant,
 }
 
 pub struct GlobalState {
@@ -23,28 +31,54 @@ pub enum Screen {
 
 impl Default for App {
     fn default() -> Self {
+        App::new(Screen::Home(screens::home::HomeState::default()))
+    }
+}
+
+impl App {
+    pub fn new(screen: Screen) -> Self {
         App {
-            state: GlobalState {
-                screen: Screen::Home(screens::home::HomeState::default()),
-            },
+            state: GlobalState { screen },
             exit: false,
+            last_autosave: Instant::now(),
         }
     }
-}
 
-impl App {
     pub fn run(
         &mut self,
         terminal: &mut crate::terminal::TerminalInterface,
     ) -> color_eyre::Result<()> {
         while !self.exit {
             terminal.draw(|frame| self.render_frame(frame).unwrap())?;
-            self.handle_events()?;
+            if event::poll(TICK_RATE)? {
+                self.handle_events(terminal)?;
+            }
+            self.autosave();
         }
-
         Ok(())
     }
 
+    /// Saves the game being played, if there is one, so it can be continued next time.
+    pub fn save_game(&self) -> std::io::Result<()> {
+        match &self.state.screen {
+            Screen::Game(game_state) => game_state.save(),
+            _ => Ok(()),
+        }
+    }
+
+    fn autosave(&mut self) {
+        if self.last_autosave.elapsed() < AUTOSAVE_INTERVAL {
+            return;
+        }
+
+        if let Screen::Game(game_state) = &self.state.screen {
+            // A failed autosave isn't worth ending the game over. The next one may work, and the
+            // save on exit reports its errors.
+            let _ = game_state.save();
+        }
+        self.last_autosave = Instant::now();
+    }
+
     fn render_frame(&self, frame: &mut Frame) -> color_eyre::Result<()> {
         match &self.state.screen {
             Screen::Home(home_state) => home_state.render_frame(frame),
@@ -54,7 +88,10 @@ impl App {
         }
     }
 
-    fn handle_events(&mut self) -> color_eyre::Result<()> {
+    fn handle_events(
+        &mut self,
+        terminal: &mut crate::terminal::TerminalInterface,
+    ) -> color_eyre::Result<()> {
         // Read any events that come from the terminal and pass them off
         // to the relevant handlers.
         let event = event::read()?;
@@ -79,11 +116,25 @@ impl App {
         match message {
             ScreenMessage::Exit => self.exit = true,
             ScreenMessage::ChangeScreen(s) => {
+                // A finished game can't be continued. Failing to delete its save only means it's
+                // offered once more, which isn't worth ending the app over.
+                if let Screen::End(_) = s {
+                    let _ = screens::game::GameState::delete_save();
+                }
                 self.state.screen = s;
             }
+            ScreenMessage::StartGame(settings) => {
+                terminal.draw(|frame| screens::game::render_generating(frame, &settings))?;
+                self.state.screen = Screen::Game(screens::game::GameState::from(settings));
+            }
             _ => {}
         }
 
+        error_handling::track_game(match &self.state.screen {
+            Screen::Game(game_state) => Some(game_state),
+            _ => None,
+        });
+
         Ok(())
     }
 }
@@ -1,3 +1,5 @@
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Position, Rect};
 // use ratatui::style::Color;
 #[derive(Clone, PartialEq, Eq)]
 pub struct Button {
@@ -12,3 +14,16 @@ impl Button {
         Button { id, text }
     }
 }
+
+/// Index of the button area a left click landed in, if any.
+pub fn clicked_button(button_areas: &[Rect], mouse: MouseEvent) -> Option<usize> {
+    if mouse.kind != MouseEventKind::Down(MouseButton::Left) {
+        return None;
+    }
+
+    let position = Position {
+        x: mouse.column,
+        y: mouse.row,
+    };
+    button_areas.iter().position(|area| area.contains(position))
+}
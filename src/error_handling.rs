@@ -1,20 +1,53 @@
 use std::panic;
+use std::sync::Mutex;
 
+use crate::screens::game::GameState;
 use crate::terminal;
 use color_eyre::eyre;
 
+// The game being played, kept up to date by the app so the hooks can save it if the app crashes.
+static CURRENT_GAME: Mutex<Option<GameState>> = Mutex::new(None);
+
+/// Keeps a copy of the game being played, or forgets it when there isn't one. The copy is only
+/// refreshed when the board or its history has changed since, so moving the cursor or the mouse
+/// doesn't clone the whole game.
+pub fn track_game(game_state: Option<&GameState>) {
+    if let Ok(mut current_game) = CURRENT_GAME.lock() {
+        let up_to_date = match (current_game.as_ref(), game_state) {
+            (Some(current), Some(game_state)) => current.revision() == game_state.revision(),
+            (None, None) => true,
+            _ => false,
+        };
+        if !up_to_date {
+            *current_game = game_state.cloned();
+        }
+    }
+}
+
+/// Writes the tracked game to the recovery file. Nothing here may panic, since it runs inside
+/// the hooks, and the lock is only tried in case the crash happened while it was held.
+fn save_recovery() {
+    if let Ok(current_game) = CURRENT_GAME.try_lock() {
+        if let Some(game_state) = current_game.as_ref() {
+            let _ = game_state.save_recovery();
+        }
+    }
+}
+
 pub fn install_hooks() -> color_eyre::Result<()> {
     let hook_builder = color_eyre::config::HookBuilder::default();
     let (panic_hook, eyre_hook) = hook_builder.into_hooks();
 
     let panic_hook = panic_hook.into_panic_hook();
     panic::set_hook(Box::new(move |panic_info| {
+        save_recovery();
         terminal::restore_terminal().unwrap();
         panic_hook(panic_info);
     }));
 
     let eyre_hook = eyre_hook.into_eyre_hook();
     let _ = eyre::set_hook(Box::new(move |error| {
+        save_recovery();
         terminal::restore_terminal().unwrap();
         eyre_hook(error)
     }));
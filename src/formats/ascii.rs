@@ -0,0 +1,109 @@
+// Puzzles drawn as plain text, a row per line, so they can be written by hand and diffed.
+//
+// `#` or `X` is a filled cell, and `.` or a space is an empty one. Short rows are padded with
+// empty cells, since editors often strip trailing spaces, and blank lines at the end of the file
+// are ignored. The clues are worked out from the drawing.
+
+use crate::screens::game::counts_from_values;
+
+use super::Puzzle;
+
+pub fn parse(text: &str) -> Result<Puzzle, String> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let mut rows = lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            line.chars()
+                .map(|c| match c {
+                    '#' | 'X' => Ok(true),
+                    '.' | ' ' => Ok(false),
+                    _ => Err(format!("line {}: didn't expect \"{}\"", index + 1, c)),
+                })
+                .collect::<Result<Vec<bool>, String>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    if width == 0 {
+        return Err(String::from("the file has no cells"));
+    }
+    for row in rows.iter_mut() {
+        row.resize(width, false);
+    }
+
+    let (row_counts, column_counts) = counts_from_values(&rows);
+    Ok(Puzzle {
+        name: None,
+        row_counts,
+        column_counts,
+        solution: Some(rows),
+    })
+}
+
+/// Draws a board as it has been filled in so far. Crossed and unknown cells are both drawn empty.
+pub fn export(values: &[Vec<Option<bool>>]) -> String {
+    let mut text = String::new();
+    for row in values {
+        text.extend(row.iter().map(|value| match value {
+            Some(true) => '#',
+            _ => '.',
+        }));
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_drawing_and_works_out_its_clues() {
+        let puzzle = parse(" # X\n#####\n.###\n  #\n\n").unwrap();
+        assert_eq!(
+            puzzle.solution,
+            Some(vec![
+                vec![false, true, false, true, false],
+                vec![true, true, true, true, true],
+                vec![false, true, true, true, false],
+                vec![false, false, true, false, false],
+            ])
+        );
+        assert_eq!(puzzle.row_counts, [vec![1, 1], vec![5], vec![3], vec![1]]);
+        assert_eq!(
+            puzzle.column_counts,
+            [vec![1], vec![3], vec![3], vec![3], vec![1]]
+        );
+    }
+
+    #[test]
+    fn exports_what_it_reads() {
+        let drawing = ".#.#.\n#####\n.###.\n..#..\n";
+        let solution = parse(drawing).unwrap().solution.unwrap();
+        let values: Vec<Vec<Option<bool>>> = solution
+            .iter()
+            .map(|row| row.iter().map(|v| Some(*v)).collect())
+            .collect();
+        assert_eq!(export(&values), drawing);
+    }
+
+    #[test]
+    fn exports_crossed_and_unknown_cells_as_empty() {
+        let values = vec![vec![Some(true), Some(false), None]];
+        assert_eq!(export(&values), "#..\n");
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            parse("..#\n.o.\n").err().unwrap(),
+            "line 2: didn't expect \"o\""
+        );
+        assert_eq!(parse("\n  \n").err().unwrap(), "the file has no cells");
+    }
+}
@@ -0,0 +1,36 @@
+// Reading and writing puzzles in file formats shared with other nonogram programs.
+
+pub mod ascii;
+pub mod non;
+pub mod webpbn;
+
+use std::{fs, path::Path};
+
+/// A puzzle read from a file: its clues, and its solution if the file has one.
+pub struct Puzzle {
+    pub name: Option<String>,
+    pub row_counts: Vec<Vec<u8>>,
+    pub column_counts: Vec<Vec<u8>>,
+    pub solution: Option<Vec<Vec<bool>>>,
+}
+
+/// Reads a puzzle file, working out its format from the extension.
+pub fn load(path: &Path) -> Result<Puzzle, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut puzzle = match path.extension().and_then(|e| e.to_str()) {
+        Some("non") => non::parse(&text)?,
+        Some("xml") | Some("pbn") => webpbn::parse(&text)?,
+        Some("txt") => ascii::parse(&text)?,
+        _ => {
+            return Err(String::from(
+                "unknown puzzle format, expected a .non, .xml or .txt file",
+            ))
+        }
+    };
+
+    // Name untitled puzzles after their file.
+    if puzzle.name.is_none() {
+        puzzle.name = path.file_stem().map(|s| s.to_string_lossy().into_owned());
+    }
+    Ok(puzzle)
+}
@@ -0,0 +1,339 @@
+// The `.non` text format.
+//
+// A file is a list of lines that each start with a keyword. `width` and `height` give the size of
+// the board, and are followed by a `rows` and a `columns` section with one clue per line, the
+// numbers separated by commas and an empty clue written as 0. An optional `goal` is the solution
+// as a string of 0s and 1s, row by row. Keywords this game has no use for, such as `by` or
+// `copyright`, are skipped.
+
+use std::str::Lines;
+
+use super::Puzzle;
+
+pub fn parse(text: &str) -> Result<Puzzle, String> {
+    let mut lines = NumberedLines {
+        lines: text.lines(),
+        line_number: 0,
+    };
+
+    let mut name = None;
+    let mut width: Option<u8> = None;
+    let mut height: Option<u8> = None;
+    let mut row_counts = None;
+    let mut column_counts = None;
+    let mut goal = None;
+
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (keyword, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+        match keyword {
+            "title" => name = Some(unquote(value).to_string()),
+            "width" => width = Some(lines.parse_size("width", value)?),
+            "height" => height = Some(lines.parse_size("height", value)?),
+            "rows" => {
+                let height = height.ok_or_else(|| lines.error("rows must come after height"))?;
+                row_counts = Some(lines.clues(height)?);
+            }
+            "columns" => {
+                let width = width.ok_or_else(|| lines.error("columns must come after width"))?;
+                column_counts = Some(lines.clues(width)?);
+            }
+            "goal" => goal = Some((lines.line_number, unquote(value).to_string())),
+            _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+            _ => return Err(lines.error(&format!("didn't expect \"{}\"", line))),
+        }
+    }
+
+    let missing = |section: &str| format!("the file has no {}", section);
+    let width = width.ok_or_else(|| missing("width"))?;
+    let height = height.ok_or_else(|| missing("height"))?;
+    let row_counts = row_counts.ok_or_else(|| missing("rows"))?;
+    let column_counts = column_counts.ok_or_else(|| missing("columns"))?;
+
+    let solution = match goal {
+        Some((line_number, goal)) => Some(parse_goal(&goal, width, height).ok_or_else(|| {
+            format!(
+                "line {}: the goal should be {} 0s and 1s",
+                line_number,
+                width as usize * height as usize
+            )
+        })?),
+        None => None,
+    };
+
+    Ok(Puzzle {
+        name,
+        row_counts,
+        column_counts,
+        solution,
+    })
+}
+
+/// Writes a puzzle out in the `.non` format. The goal is only written if the puzzle has a
+/// solution.
+pub fn export(puzzle: &Puzzle) -> String {
+    let mut lines = Vec::new();
+    if let Some(name) = &puzzle.name {
+        lines.push(format!("title \"{}\"", name));
+    }
+    lines.push(format!("width {}", puzzle.column_counts.len()));
+    lines.push(format!("height {}", puzzle.row_counts.len()));
+
+    for (section, clues) in [
+        ("rows", &puzzle.row_counts),
+        ("columns", &puzzle.column_counts),
+    ] {
+        lines.push(String::new());
+        lines.push(String::from(section));
+        for clue in clues {
+            let numbers: Vec<String> = clue.iter().map(|n| n.to_string()).collect();
+            lines.push(match numbers.is_empty() {
+                true => String::from("0"),
+                false => numbers.join(","),
+            });
+        }
+    }
+
+    if let Some(solution) = &puzzle.solution {
+        let goal: String = solution
+            .iter()
+            .flatten()
+            .map(|filled| match filled {
+                true => '1',
+                false => '0',
+            })
+            .collect();
+        lines.push(String::new());
+        lines.push(format!("goal \"{}\"", goal));
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Lines of the file, keeping track of the line number for errors.
+struct NumberedLines<'a> {
+    lines: Lines<'a>,
+    line_number: usize,
+}
+
+impl<'a> NumberedLines<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        self.line_number += 1;
+        self.lines.next()
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line_number, message)
+    }
+
+    fn parse_size(&self, keyword: &str, value: &str) -> Result<u8, String> {
+        value
+            .parse()
+            .ok()
+            .filter(|size| *size > 0)
+            .ok_or_else(|| self.error(&format!("{} should be a number from 1 to 255", keyword)))
+    }
+
+    /// Reads the next `count` clues, skipping blank lines.
+    fn clues(&mut self, count: u8) -> Result<Vec<Vec<u8>>, String> {
+        let mut clues = Vec::with_capacity(count as usize);
+        while clues.len() < count as usize {
+            let Some(line) = self.next() else {
+                return Err(self.error(&format!(
+                    "the file ended after {} of {} clues",
+                    clues.len(),
+                    count
+                )));
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let clue = parse_clue(line)
+                .ok_or_else(|| self.error(&format!("couldn't read the clue \"{}\"", line)))?;
+            clues.push(clue);
+        }
+        Ok(clues)
+    }
+}
+
+/// Reads a clue such as `3,1,2`. A clue of `0` is an empty line.
+fn parse_clue(line: &str) -> Option<Vec<u8>> {
+    let numbers: Vec<u8> = line
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+
+    match numbers.as_slice() {
+        [0] => Some(Vec::new()),
+        _ if numbers.contains(&0) => None,
+        _ => Some(numbers),
+    }
+}
+
+fn parse_goal(goal: &str, width: u8, height: u8) -> Option<Vec<Vec<bool>>> {
+    let cells: Vec<bool> = goal
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '1' => Some(true),
+            '0' => Some(false),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    if cells.len() != width as usize * height as usize {
+        return None;
+    }
+    Some(
+        cells
+            .chunks(width as usize)
+            .map(|row| row.to_vec())
+            .collect(),
+    )
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screens::game::GameState;
+
+    const HEART: &str = "catalogue \"examples\"
+title \"Heart\"
+by \"someone\"
+width 5
+height 4
+
+rows
+1,1
+5
+3
+1
+
+columns
+1
+3
+3
+3
+1
+";
+
+    #[test]
+    fn reads_the_clues_and_skips_other_keywords() {
+        let puzzle = parse(HEART).unwrap();
+        assert_eq!(puzzle.name.as_deref(), Some("Heart"));
+        assert_eq!(puzzle.row_counts, [vec![1, 1], vec![5], vec![3], vec![1]]);
+        assert_eq!(
+            puzzle.column_counts,
+            [vec![1], vec![3], vec![3], vec![3], vec![1]]
+        );
+        assert!(puzzle.solution.is_none());
+    }
+
+    #[test]
+    fn exports_what_it_reads() {
+        let puzzle = GameState::from_puzzle(parse(HEART).unwrap())
+            .unwrap()
+            .to_puzzle();
+        let exported = export(&puzzle);
+        assert!(exported.contains("goal \"01010111110111000100\""));
+
+        let read_back = parse(&exported).unwrap();
+        assert_eq!(read_back.name, puzzle.name);
+        assert_eq!(read_back.row_counts, puzzle.row_counts);
+        assert_eq!(read_back.column_counts, puzzle.column_counts);
+        assert_eq!(read_back.solution, puzzle.solution);
+        assert_eq!(export(&read_back), exported);
+    }
+
+    #[test]
+    fn empty_clues_are_written_as_zero() {
+        let puzzle = Puzzle {
+            name: None,
+            row_counts: vec![vec![], vec![2]],
+            column_counts: vec![vec![1], vec![1]],
+            solution: Some(vec![vec![false, false], vec![true, true]]),
+        };
+        let exported = export(&puzzle);
+        assert_eq!(
+            exported,
+            "width 2\nheight 2\n\nrows\n0\n2\n\ncolumns\n1\n1\n\ngoal \"0011\"\n"
+        );
+        assert_eq!(parse(&exported).unwrap().row_counts, puzzle.row_counts);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |text: &str| parse(text).err().unwrap();
+
+        assert_eq!(
+            error("width 5\nheight 2\nrows\n1\n1,x\n"),
+            "line 5: couldn't read the clue \"1,x\""
+        );
+        assert_eq!(
+            error("width 5\nheight 2\n\nrows\n1\n"),
+            "line 6: the file ended after 1 of 2 clues"
+        );
+        assert_eq!(
+            error("title \"t\"\nwidth 0\n"),
+            "line 2: width should be a number from 1 to 255"
+        );
+        assert_eq!(error("rows\n1\n"), "line 1: rows must come after height");
+        assert_eq!(error("width 1\n5\n"), "line 2: didn't expect \"5\"");
+        assert_eq!(
+            error("width 2\nheight 1\nrows\n1\ncolumns\n1\n0\ngoal \"1\"\n"),
+            "line 8: the goal should be 2 0s and 1s"
+        );
+        assert_eq!(
+            error("width 2\nheight 1\nrows\n1\n"),
+            "the file has no columns"
+        );
+    }
+
+    #[test]
+    fn a_goal_has_to_match_the_clues() {
+        let text = "width 2\nheight 2\nrows\n1\n1\ncolumns\n1\n1\n";
+        let matching = format!("{}goal 1001\n", text);
+        assert!(GameState::from_puzzle(parse(&matching).unwrap()).is_ok());
+
+        let wrong = format!("{}goal 1101\n", text);
+        assert_eq!(
+            GameState::from_puzzle(parse(&wrong).unwrap())
+                .err()
+                .unwrap(),
+            "the solution doesn't match the clues"
+        );
+    }
+
+    #[test]
+    fn clues_without_a_goal_are_solved_on_import() {
+        let game_state = GameState::from_puzzle(parse(HEART).unwrap()).unwrap();
+        assert_eq!(
+            game_state.board_state.true_values[1],
+            [true, true, true, true, true]
+        );
+
+        let impossible = "width 2\nheight 2\nrows\n2\n2\ncolumns\n1\n1\n";
+        assert_eq!(
+            GameState::from_puzzle(parse(impossible).unwrap())
+                .err()
+                .unwrap(),
+            "the clues have no solution"
+        );
+    }
+}
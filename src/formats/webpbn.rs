@@ -0,0 +1,525 @@
+// The XML format used by webpbn.com and many puzzle collections.
+//
+// A file holds a `<puzzleset>` with one or more `<puzzle>` elements, of which the first is read.
+// Each puzzle has a `<clues type="rows">` and a `<clues type="columns">` element, made of a `<line>`
+// per clue with a `<count>` per number. An optional `<solution>` is an `<image>` of the board, a
+// row per line between `|` characters, drawn with the `char` of each `<color>`.
+//
+// There's no XML parser in the dependencies, so the file is read with a small tokenizer that only
+// knows what this format needs: elements, attributes, text, and the parts to skip.
+
+use super::Puzzle;
+
+// What the format uses when a puzzle doesn't name its colors.
+const DEFAULT_BACKGROUND: &str = "white";
+const DEFAULT_FOREGROUND: &str = "black";
+const DEFAULT_BACKGROUND_CHAR: char = '.';
+const DEFAULT_FOREGROUND_CHAR: char = 'X';
+
+pub fn parse(text: &str) -> Result<Puzzle, String> {
+    let mut tokens = Tokenizer {
+        text,
+        position: 0,
+        line_number: 1,
+        pending_end: None,
+    };
+
+    let mut in_puzzle = false;
+    let mut background = String::from(DEFAULT_BACKGROUND);
+    let mut foreground = String::from(DEFAULT_FOREGROUND);
+    // Each color's name and the character it's drawn with in the solution image.
+    let mut colors: Vec<(String, Option<char>)> = Vec::new();
+
+    let mut name = None;
+    let mut row_counts = None;
+    let mut column_counts = None;
+    let mut clues: Option<(bool, Vec<Vec<u8>>)> = None;
+    let mut image = None;
+    // The element whose text is being read, with the line it started on.
+    let mut reading: Option<(&str, usize)> = None;
+    // Whether the solution being read is the goal, rather than saved progress.
+    let mut in_goal = false;
+
+    while let Some(token) = tokens.next()? {
+        let line_number = tokens.line_number;
+        let error = |message: &str| format!("line {}: {}", line_number, message);
+
+        match token {
+            Token::Start(element) if !in_puzzle => {
+                if element.name == "puzzle" {
+                    in_puzzle = true;
+                    if let Some(t) = element.attribute("type").filter(|t| *t != "grid") {
+                        return Err(error(&format!("{} puzzles aren't supported", t)));
+                    }
+                    if let Some(color) = element.attribute("backgroundcolor") {
+                        background = color.to_string();
+                    }
+                    if let Some(color) = element.attribute("defaultcolor") {
+                        foreground = color.to_string();
+                    }
+                }
+            }
+            Token::Start(element) => match element.name {
+                "count" if element.attribute("color").is_some_and(|c| c != foreground) => {
+                    return Err(error("colored puzzles aren't supported yet"));
+                }
+                "title" | "count" | "image" => reading = Some((element.name, line_number)),
+                "color" => {
+                    let color = element
+                        .attribute("name")
+                        .ok_or_else(|| error("a color has no name"))?;
+                    let symbol = element.attribute("char").and_then(single_char);
+                    colors.push((color.to_string(), symbol));
+                }
+                "clues" => {
+                    let rows = match element.attribute("type") {
+                        Some("rows") => true,
+                        Some("columns") => false,
+                        _ => return Err(error("clues should have a type of rows or columns")),
+                    };
+                    clues = Some((rows, Vec::new()));
+                }
+                "line" => match &mut clues {
+                    Some((_, lines)) => lines.push(Vec::new()),
+                    None => return Err(error("a line should be inside clues")),
+                },
+                "solution" => in_goal = element.attribute("type").is_none_or(|t| t == "goal"),
+                _ => {}
+            },
+            Token::Text(text) => {
+                let Some((element, start)) = reading else {
+                    continue;
+                };
+                let error = |message: &str| format!("line {}: {}", start, message);
+                match element {
+                    "title" => name = Some(text.trim().to_string()),
+                    "count" => {
+                        let count =
+                            text.trim().parse().ok().filter(|c| *c > 0).ok_or_else(|| {
+                                error(&format!("couldn't read the count \"{}\"", text))
+                            })?;
+                        match clues.as_mut().and_then(|(_, lines)| lines.last_mut()) {
+                            Some(line) => line.push(count),
+                            None => return Err(error("a count should be inside a line")),
+                        }
+                    }
+                    "image" if in_goal && image.is_none() => image = Some((start, text)),
+                    _ => {}
+                }
+            }
+            Token::End(element) => match element {
+                "title" | "count" | "image" => reading = None,
+                "clues" => match clues.take() {
+                    Some((true, lines)) => row_counts = Some(lines),
+                    Some((false, lines)) => column_counts = Some(lines),
+                    None => {}
+                },
+                "solution" => in_goal = false,
+                "puzzle" if in_puzzle => break,
+                _ => {}
+            },
+        }
+    }
+
+    if !in_puzzle {
+        return Err(String::from("the file has no puzzle"));
+    }
+    let missing = |section: &str| format!("the puzzle has no {} clues", section);
+    let row_counts = row_counts.ok_or_else(|| missing("row"))?;
+    let column_counts = column_counts.ok_or_else(|| missing("column"))?;
+
+    // Anything other than the background and one color needs multi-color support.
+    let other_colors: Vec<&str> = colors
+        .iter()
+        .map(|(color, _)| color.as_str())
+        .filter(|color| *color != background && *color != foreground)
+        .collect();
+    if !other_colors.is_empty() {
+        return Err(format!(
+            "colored puzzles aren't supported yet, this one uses {}",
+            other_colors.join(", ")
+        ));
+    }
+
+    let symbol = |color: &str, default: char| {
+        colors
+            .iter()
+            .find(|(name, _)| name == color)
+            .and_then(|(_, symbol)| *symbol)
+            .unwrap_or(default)
+    };
+    let empty = symbol(&background, DEFAULT_BACKGROUND_CHAR);
+    let filled = symbol(&foreground, DEFAULT_FOREGROUND_CHAR);
+
+    let solution = match image {
+        Some((line_number, image)) => Some(
+            parse_image(&image, empty, filled, column_counts.len(), row_counts.len())
+                .map_err(|message| format!("line {}: {}", line_number, message))?,
+        ),
+        None => None,
+    };
+
+    Ok(Puzzle {
+        name: name.filter(|name| !name.is_empty()),
+        row_counts,
+        column_counts,
+        solution,
+    })
+}
+
+/// Reads a solution image, a row per line with the cells between `|` characters.
+fn parse_image(
+    image: &str,
+    empty: char,
+    filled: char,
+    width: usize,
+    height: usize,
+) -> Result<Vec<Vec<bool>>, String> {
+    let rows: Vec<Vec<bool>> = image
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.trim_matches('|')
+                .chars()
+                .map(|c| match c {
+                    _ if c == filled => Ok(true),
+                    _ if c == empty => Ok(false),
+                    _ => Err(format!("the solution has an unknown cell \"{}\"", c)),
+                })
+                .collect()
+        })
+        .collect::<Result<_, _>>()?;
+
+    if rows.len() != height || rows.iter().any(|row| row.len() != width) {
+        return Err(format!(
+            "the solution should be {} cells wide and {} tall",
+            width, height
+        ));
+    }
+    Ok(rows)
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+enum Token<'a> {
+    Start(Element<'a>),
+    End(&'a str),
+    Text(String),
+}
+
+struct Element<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+}
+
+impl Element<'_> {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits a document into tags and text. The declaration, doctype, comments and processing
+/// instructions are skipped, and a self-closing tag is read as a start followed by an end.
+struct Tokenizer<'a> {
+    text: &'a str,
+    position: usize,
+    // The line `position` is on, counted as it moves so errors don't rescan the text.
+    line_number: usize,
+    pending_end: Option<&'a str>,
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Moves past the next `length` bytes of the text.
+    fn advance(&mut self, length: usize) {
+        let skipped = &self.text[self.position..self.position + length];
+        self.line_number += skipped.matches('\n').count();
+        self.position += length;
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line_number, message)
+    }
+
+    fn next(&mut self) -> Result<Option<Token<'a>>, String> {
+        if let Some(name) = self.pending_end.take() {
+            return Ok(Some(Token::End(name)));
+        }
+
+        loop {
+            let rest = &self.text[self.position..];
+            if rest.is_empty() {
+                return Ok(None);
+            }
+
+            if !rest.starts_with('<') {
+                let length = rest.find('<').unwrap_or(rest.len());
+                let text = decode(&rest[..length]).ok_or_else(|| self.error("unknown entity"))?;
+                self.advance(length);
+                return Ok(Some(Token::Text(text)));
+            }
+
+            let skipped = [("<!--", "-->"), ("<?", "?>"), ("<!", ">")]
+                .into_iter()
+                .find(|(start, _)| rest.starts_with(start));
+            if let Some((start, end)) = skipped {
+                let length = rest
+                    .find(end)
+                    .ok_or_else(|| self.error(&format!("{} is never closed", start)))?;
+                self.advance(length + end.len());
+                continue;
+            }
+
+            let length = rest
+                .find('>')
+                .ok_or_else(|| self.error("a tag is never closed"))?;
+            let tag = &rest[1..length];
+            self.advance(length + 1);
+
+            if let Some(name) = tag.strip_prefix('/') {
+                return Ok(Some(Token::End(name.trim())));
+            }
+            let (tag, self_closing) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let element = self.parse_element(tag)?;
+            if self_closing {
+                self.pending_end = Some(element.name);
+            }
+            return Ok(Some(Token::Start(element)));
+        }
+    }
+
+    /// Reads the name and attributes of a start tag, such as `clues type="rows"`.
+    fn parse_element(&self, tag: &'a str) -> Result<Element<'a>, String> {
+        let tag = tag.trim();
+        let name_length = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let name = &tag[..name_length];
+        if name.is_empty() {
+            return Err(self.error("a tag has no name"));
+        }
+
+        let mut attributes = Vec::new();
+        let mut rest = tag[name_length..].trim_start();
+        while !rest.is_empty() {
+            let bad_attribute =
+                || self.error(&format!("couldn't read the attributes of <{}>", name));
+            let (attribute, value) = rest.split_once('=').ok_or_else(bad_attribute)?;
+            let value = value.trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(bad_attribute)?;
+            let value = &value[1..];
+            let length = value.find(quote).ok_or_else(bad_attribute)?;
+            let decoded = decode(&value[..length]).ok_or_else(|| self.error("unknown entity"))?;
+            attributes.push((attribute.trim(), decoded));
+            rest = value[length + 1..].trim_start();
+        }
+
+        Ok(Element { name, attributes })
+    }
+}
+
+/// Replaces character and entity references with the characters they stand for.
+fn decode(text: &str) -> Option<String> {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        let length = rest[start..].find(';')?;
+        let entity = &rest[start + 1..start + length];
+        decoded.push(match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        });
+        rest = &rest[start + length + 1..];
+    }
+    decoded.push_str(rest);
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screens::game::GameState;
+
+    const PUZZLE: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE pbn SYSTEM "https://webpbn.com/pbn-0.3.dtd">
+<puzzleset>
+<puzzle type="grid" defaultcolor="black">
+<source>webpbn.com</source>
+<title>Cup &amp; &quot;Saucer&quot;</title>
+<!-- <clues> in a comment are skipped -->
+<color name="white" char=".">fff</color>
+<color name="black" char="X">000</color>
+<clues type="columns">
+<line><count>1</count><count>1</count></line>
+<line><count>2</count></line>
+<line><count>1</count><count>1</count></line>
+</clues>
+<clues type='rows'>
+<line><count>1</count><count>1</count></line>
+<line><count>1</count></line>
+<line><count>3</count></line>
+</clues>
+<solution type="goal">
+<image>
+|X.X|
+|.X.|
+|XXX|
+</image>
+</solution>
+</puzzle>
+<puzzle><title>Second</title></puzzle>
+</puzzleset>
+"#;
+
+    #[test]
+    fn reads_the_first_puzzle() {
+        let puzzle = parse(PUZZLE).unwrap();
+        assert_eq!(puzzle.name.as_deref(), Some("Cup & \"Saucer\""));
+        assert_eq!(puzzle.row_counts, [vec![1, 1], vec![1], vec![3]]);
+        assert_eq!(puzzle.column_counts, [vec![1, 1], vec![2], vec![1, 1]]);
+        assert_eq!(
+            puzzle.solution,
+            Some(vec![
+                vec![true, false, true],
+                vec![false, true, false],
+                vec![true, true, true]
+            ])
+        );
+        assert!(GameState::from_puzzle(puzzle).is_ok());
+    }
+
+    #[test]
+    fn self_closing_lines_are_empty_clues() {
+        let text = "<puzzle><clues type=\"rows\"><line/><line><count>1</count></line></clues>\
+            <clues type=\"columns\"><line /><line><count>1</count></line></clues></puzzle>";
+        let puzzle = parse(text).unwrap();
+        assert_eq!(puzzle.row_counts, [vec![], vec![1]]);
+        assert_eq!(puzzle.column_counts, [vec![], vec![1]]);
+        assert!(puzzle.name.is_none());
+    }
+
+    #[test]
+    fn decodes_entities() {
+        assert_eq!(
+            decode("&lt;a&gt; &#65;&#x42; &apos;").as_deref(),
+            Some("<a> AB '")
+        );
+        assert_eq!(decode("&nbsp;"), None);
+        assert_eq!(decode("&amp"), None);
+
+        let text = PUZZLE.replace("Cup &amp;", "Cup &bogus;");
+        assert_eq!(parse(&text).err().unwrap(), "line 6: unknown entity");
+    }
+
+    #[test]
+    fn rejects_colored_puzzles() {
+        let extra_color = PUZZLE.replace(
+            "<color name=\"black\" char=\"X\">000</color>",
+            "<color name=\"black\" char=\"X\">000</color>\n<color name=\"red\" char=\"r\">f00</color>",
+        );
+        assert_eq!(
+            parse(&extra_color).err().unwrap(),
+            "colored puzzles aren't supported yet, this one uses red"
+        );
+
+        let colored_count = PUZZLE.replace("<count>2</count>", "<count color=\"red\">2</count>");
+        assert_eq!(
+            parse(&colored_count).err().unwrap(),
+            "line 12: colored puzzles aren't supported yet"
+        );
+    }
+
+    #[test]
+    fn only_the_goal_is_taken_as_the_solution() {
+        let saved = PUZZLE.replace("type=\"goal\"", "type=\"saved\"");
+        assert!(parse(&saved).unwrap().solution.is_none());
+    }
+
+    #[test]
+    fn reads_the_solution_with_the_puzzle_s_own_characters() {
+        let text = PUZZLE
+            .replace("char=\".\"", "char=\"-\"")
+            .replace("char=\"X\"", "char=\"#\"")
+            .replace("|X.X|\n|.X.|\n|XXX|", "|#-#|\n|-#-|\n|###|");
+        assert_eq!(
+            parse(&text).unwrap().solution,
+            parse(PUZZLE).unwrap().solution
+        );
+    }
+
+    #[test]
+    fn a_solution_has_to_match_the_clues() {
+        let wrong = PUZZLE.replace("|.X.|", "|..X|");
+        assert_eq!(
+            GameState::from_puzzle(parse(&wrong).unwrap())
+                .err()
+                .unwrap(),
+            "the solution doesn't match the clues"
+        );
+
+        let short = PUZZLE.replace("|.X.|", "|.X|");
+        assert_eq!(
+            parse(&short).err().unwrap(),
+            "line 21: the solution should be 3 cells wide and 3 tall"
+        );
+
+        let unknown = PUZZLE.replace("|.X.|", "|.Y.|");
+        assert_eq!(
+            parse(&unknown).err().unwrap(),
+            "line 21: the solution has an unknown cell \"Y\""
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            parse(&PUZZLE.replace("<count>2</count>", "<count>two</count>"))
+                .err()
+                .unwrap(),
+            "line 12: couldn't read the count \"two\""
+        );
+        assert_eq!(
+            parse(&PUZZLE.replace("<clues type='rows'>", "<clues>"))
+                .err()
+                .unwrap(),
+            "line 15: clues should have a type of rows or columns"
+        );
+        assert_eq!(
+            parse("<puzzle>\n<title>x</title>\n<clues type=\"rows\"")
+                .err()
+                .unwrap(),
+            "line 3: a tag is never closed"
+        );
+        assert_eq!(
+            parse("<puzzleset/>").err().unwrap(),
+            "the file has no puzzle"
+        );
+        assert_eq!(
+            parse("<puzzle type=\"triddler\"></puzzle>").err().unwrap(),
+            "line 1: triddler puzzles aren't supported"
+        );
+    }
+}
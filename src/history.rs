@@ -0,0 +1,162 @@
+// Undo and redo for edits to the board.
+//
+// Every edit is recorded as the list of cells it changed, with their values before and after, so
+// an edit that touches many cells at once is undone in a single step.
+
+#[derive(Clone, Copy)]
+pub struct CellChange {
+    pub row: u8,
+    pub column: u8,
+    pub before: Option<bool>,
+    pub after: Option<bool>,
+}
+
+#[derive(Clone, Default)]
+pub struct History {
+    undo: Vec<Vec<CellChange>>,
+    redo: Vec<Vec<CellChange>>,
+    // While set, recorded changes are added to the latest edit instead of starting a new one.
+    joining: bool,
+}
+
+impl History {
+    /// Records an edit. Anything that could have been redone is dropped, since it no longer
+    /// follows on from the board.
+    pub fn record(&mut self, changes: Vec<CellChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        match (self.joining, self.undo.last_mut()) {
+            (true, Some(latest)) => latest.extend(changes),
+            _ => self.undo.push(changes),
+        }
+        self.redo.clear();
+    }
+
+    /// Makes the following edits part of the latest one, until `stop_joining` is called.
+    pub fn join_next(&mut self) {
+        self.joining = true;
+    }
+
+    pub fn stop_joining(&mut self) {
+        self.joining = false;
+    }
+
+    /// The edits that can be undone and redone, oldest first on each stack.
+    pub fn edits(&self) -> (&[Vec<CellChange>], &[Vec<CellChange>]) {
+        (&self.undo, &self.redo)
+    }
+
+    /// Puts back a history from the edits returned by `edits`.
+    pub fn from_edits(undo: Vec<Vec<CellChange>>, redo: Vec<Vec<CellChange>>) -> Self {
+        History {
+            undo,
+            redo,
+            joining: false,
+        }
+    }
+
+    /// Takes the latest edit off the undo stack. The caller puts back the `before` values.
+    pub fn undo(&mut self) -> Option<&[CellChange]> {
+        self.joining = false;
+        let changes = self.undo.pop()?;
+        self.redo.push(changes);
+        self.redo.last().map(|c| c.as_slice())
+    }
+
+    /// Takes the latest undone edit off the redo stack. The caller puts back the `after` values.
+    pub fn redo(&mut self) -> Option<&[CellChange]> {
+        self.joining = false;
+        let changes = self.redo.pop()?;
+        self.undo.push(changes);
+        self.undo.last().map(|c| c.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(row: u8, column: u8) -> CellChange {
+        CellChange {
+            row,
+            column,
+            before: None,
+            after: Some(true),
+        }
+    }
+
+    fn cells(changes: &[CellChange]) -> Vec<(u8, u8)> {
+        changes.iter().map(|c| (c.row, c.column)).collect()
+    }
+
+    fn edit_counts(history: &History) -> (usize, usize) {
+        let (undo, redo) = history.edits();
+        (undo.len(), redo.len())
+    }
+
+    #[test]
+    fn undo_and_redo_move_edits_between_the_stacks() {
+        let mut history = History::default();
+        history.record(vec![fill(0, 0)]);
+        history.record(vec![fill(1, 1)]);
+
+        assert_eq!(cells(history.undo().unwrap()), [(1, 1)]);
+        assert_eq!(cells(history.undo().unwrap()), [(0, 0)]);
+        assert!(history.undo().is_none());
+        assert_eq!(edit_counts(&history), (0, 2));
+
+        assert_eq!(cells(history.redo().unwrap()), [(0, 0)]);
+        assert_eq!(edit_counts(&history), (1, 1));
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut history = History::default();
+        history.record(vec![fill(0, 0)]);
+        history.record(vec![fill(1, 1)]);
+        history.undo();
+
+        history.record(vec![fill(2, 2)]);
+        assert_eq!(edit_counts(&history), (2, 0));
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn an_edit_that_changes_nothing_is_not_recorded() {
+        let mut history = History::default();
+        history.record(vec![fill(0, 0)]);
+        history.undo();
+
+        history.record(Vec::new());
+        assert_eq!(edit_counts(&history), (0, 1));
+    }
+
+    #[test]
+    fn joined_changes_are_undone_as_one_edit() {
+        let mut history = History::default();
+        history.record(vec![fill(0, 0)]);
+        history.join_next();
+        history.record(vec![fill(0, 1)]);
+        history.record(vec![fill(0, 2)]);
+        history.stop_joining();
+        history.record(vec![fill(1, 0)]);
+
+        assert_eq!(edit_counts(&history), (2, 0));
+        assert_eq!(cells(history.undo().unwrap()), [(1, 0)]);
+        assert_eq!(cells(history.undo().unwrap()), [(0, 0), (0, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn undoing_stops_joining() {
+        let mut history = History::default();
+        history.record(vec![fill(0, 0)]);
+        history.record(vec![fill(0, 1)]);
+        history.join_next();
+        history.undo();
+
+        history.record(vec![fill(1, 1)]);
+        assert_eq!(edit_counts(&history), (2, 0));
+        assert_eq!(cells(history.undo().unwrap()), [(1, 1)]);
+    }
+}
@@ -1,15 +1,40 @@
 mod app;
 mod components;
 mod error_handling;
+mod formats;
+mod history;
 mod screens;
+mod solver;
+mod storage;
 mod terminal;
 mod traits;
-mod tui;
+
+use std::path::PathBuf;
+
+use color_eyre::eyre::eyre;
 
 fn main() -> color_eyre::Result<()> {
     error_handling::install_hooks()?;
+
+    // A puzzle file given on the command line is played straight away.
+    let mut app = match std::env::args_os().nth(1).map(PathBuf::from) {
+        Some(path) => {
+            let game_state = formats::load(&path)
+                .and_then(screens::game::GameState::from_puzzle)
+                .map_err(|e| eyre!("Couldn't import {}: {}", path.display(), e))?;
+            app::App::new(app::Screen::Game(game_state))
+        }
+        None => app::App::default(),
+    };
+
     let mut terminal = terminal::init_terminal()?;
-    let app_result = app::App::default().run(&mut terminal);
+    let app_result = app.run(&mut terminal);
     terminal::restore_terminal()?;
-    app_result
+    app_result?;
+
+    // Reported once the terminal is back to normal, since there's nothing left to crash out of.
+    if let Err(error) = app.save_game() {
+        eprintln!("Couldn't save the game: {}", error);
+    }
+    Ok(())
 }
@@ -1,64 +1,126 @@
+use crossterm::event::{self, Event, KeyCode};
 use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::text::Line;
 use ratatui::widgets::Paragraph;
+use std::time::Duration;
 
-use crate::screens::game::GameState;
-use crate::traits::{EventHandler, FrameRenderer};
+use crate::app::Screen;
+use crate::screens::game::{self, GameState};
+use crate::screens::home::HomeState;
+use crate::solver::Technique;
+use crate::traits::{EventHandler, FrameRenderer, ScreenMessage};
 
 pub struct EndState {
     end_game_state: GameState,
+    outcome: Outcome,
+    // Time spent on the game when it ended.
+    time: Duration,
+    // Worked out once when the game ends, since solving the board again on every redraw is slow.
+    stats: EndGameStats,
+}
+
+enum Outcome {
+    Solved,
+    OutOfLives,
 }
 
-#[derive(Debug)]
 struct EndGameStats {
     total_squares: u16,
     black_squares: u16,
+    solver_passes: u32,
+    hardest_technique: Option<Technique>,
 }
 
-impl EndState {
-    fn get_game_stats(&self) -> EndGameStats {
-        let total_squares =
-            (self.end_game_state.settings.size * self.end_game_state.settings.size) as u16;
-        let black_squares = self
-            .end_game_state
-            .board_state
-            .true_values
-            .iter()
-            .fold(0, |acc, e| {
-                acc + e
-                    .iter()
-                    .map(|x| match x {
-                        true => 1,
-                        false => 0,
-                    })
-                    .sum::<u16>()
-            });
+impl From<&GameState> for EndGameStats {
+    fn from(game_state: &GameState) -> Self {
+        let total_squares = game_state.settings.width as u16 * game_state.settings.height as u16;
+        let black_squares = game_state.board_state.true_values.iter().fold(0, |acc, e| {
+            acc + e
+                .iter()
+                .map(|x| match x {
+                    true => 1,
+                    false => 0,
+                })
+                .sum::<u16>()
+        });
+
+        let report = game_state.board_state.line_solve_report();
 
-        return EndGameStats {
+        EndGameStats {
             total_squares,
             black_squares,
-        };
+            solver_passes: report.passes,
+            hardest_technique: report.hardest_technique(),
+        }
     }
 }
 
 impl From<GameState> for EndState {
     fn from(game_state: GameState) -> Self {
-        return EndState {
-            end_game_state: game_state,
+        let outcome = match game_state.lives {
+            Some(0) => Outcome::OutOfLives,
+            _ => Outcome::Solved,
         };
+
+        EndState {
+            time: game_state.elapsed(),
+            stats: EndGameStats::from(&game_state),
+            end_game_state: game_state,
+            outcome,
+        }
     }
 }
 
-impl EventHandler for EndState {}
+impl EventHandler for EndState {
+    fn handle_events(&mut self, event: Event) -> color_eyre::Result<ScreenMessage> {
+        if let Event::Key(key) = event {
+            if key.kind == event::KeyEventKind::Release {
+                return Ok(ScreenMessage::Noop);
+            }
+
+            return match key.code {
+                KeyCode::Enter => Ok(ScreenMessage::ChangeScreen(Screen::Home(
+                    HomeState::default(),
+                ))),
+                _ => Ok(ScreenMessage::Noop),
+            };
+        }
+
+        Ok(ScreenMessage::Noop)
+    }
+}
 
 impl FrameRenderer for EndState {
     fn render_frame(&self, frame: &mut ratatui::prelude::Frame) -> color_eyre::Result<()> {
         let layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Percentage(20)])
+            .constraints(vec![Constraint::Min(0)])
             .split(frame.size());
+        let stats = &self.stats;
 
         frame.render_widget(
-            Paragraph::new(format!("{:?}", self.get_game_stats())),
+            Paragraph::new(vec![
+                Line::from(match self.outcome {
+                    Outcome::Solved => format!(
+                        "Solved! {} of {} squares filled.",
+                        stats.black_squares, stats.total_squares
+                    ),
+                    Outcome::OutOfLives => format!(
+                        "Out of lives! The solution had {} of {} squares filled.",
+                        stats.black_squares, stats.total_squares
+                    ),
+                }),
+                Line::from(match stats.hardest_technique {
+                    Some(technique) => format!(
+                        "Line logic needed {} passes, using up to {}.",
+                        stats.solver_passes, technique
+                    ),
+                    None => String::from("Line logic had nothing to do."),
+                }),
+                Line::from(format!("Time: {}", game::format_duration(self.time))),
+                Line::from(self.end_game_state.puzzle_text()),
+                Line::from("Press Enter to return to the menu."),
+            ]),
             layout[0],
         );
 
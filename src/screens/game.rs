@@ -1,15 +1,26 @@
-use std::ops::Deref;
+mod save;
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event, KeyCode};
-use rand::Rng;
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     prelude::*,
-    widgets::Paragraph,
+    widgets::{Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
 };
 
 use crate::{
     app::Screen,
+    formats::{self, Puzzle},
+    history::{CellChange, History},
+    solver, storage, terminal,
     traits::{EventHandler, FrameRenderer, ScreenMessage},
 };
 
@@ -17,23 +28,199 @@ use super::end::EndState;
 
 #[derive(Clone, Copy)]
 pub struct GameSettings {
-    pub size: u8,
+    pub width: u8,
+    pub height: u8,
+    pub difficulty: Difficulty,
+    // Share of the cells that are filled. The generator's repairs fill or clear cells to keep
+    // close to it, but big boards can't be as sparse as small ones, see `min_density`.
+    pub density: f64,
+    // The same settings and seed always generate the same board.
+    pub seed: u64,
+    // Only check the board when the player presses 'c', instead of after every edit.
+    pub strict_check: bool,
+    pub mode: GameMode,
+    // Terminal columns each cell of the board takes, from 1 to 3.
+    pub cell_width: u8,
+    // Shade the selected square's row and column.
+    pub crosshair: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings {
+            width: 10,
+            height: 10,
+            difficulty: Difficulty::Medium,
+            density: 0.5,
+            seed: rand::random(),
+            strict_check: false,
+            mode: GameMode::Classic,
+            cell_width: 1,
+            crosshair: false,
+        }
+    }
+}
+
+impl GameSettings {
+    /// Everything the generated board depends on, such as `10x10 d50 Medium #1234`. The same
+    /// code always names the same board.
+    pub fn puzzle_code(&self) -> String {
+        format!(
+            "{}x{} d{} {} #{}",
+            self.width,
+            self.height,
+            (self.density * 100.0).round(),
+            self.difficulty,
+            self.seed
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    Classic,
+    // Filling a cell that should be empty crosses it instead and costs a life. The game is lost
+    // when the lives run out.
+    Lives,
+}
+
+impl std::fmt::Display for GameMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// Where the 'e' key writes the puzzle and the 'a' key draws the board, in the data directory.
+const EXPORT_FILE: &str = "export.non";
+const BOARD_EXPORT_FILE: &str = "export.txt";
+
+// Lives a game in lives mode starts with.
+const STARTING_LIVES: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Medium,
+            Difficulty::Medium => Difficulty::Hard,
+            Difficulty::Hard | Difficulty::Expert => Difficulty::Expert,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        match self {
+            Difficulty::Easy | Difficulty::Medium => Difficulty::Easy,
+            Difficulty::Hard => Difficulty::Medium,
+            Difficulty::Expert => Difficulty::Hard,
+        }
+    }
+
+    /// Rates a line-solvable board by how hard the solver had to work on it: how many sweeps over
+    /// the board it took, and what share of the cells needed a search through every arrangement of
+    /// a line rather than one of the simpler techniques. Both grow with the size of the board, so
+    /// the limits are scaled to it, taking a 10x10 board as the baseline.
+    fn rate(report: &solver::LineSolveReport) -> Self {
+        let height = report.grid.len();
+        let width = report.grid.first().map_or(0, |row| row.len());
+        let size = width.max(height).max(1);
+        let cells = width * height;
+
+        let exhaustive = report
+            .deductions
+            .iter()
+            .filter(|d| d.technique == solver::Technique::Exhaustive)
+            .count();
+        let exhaustive_permille = exhaustive * 1000 / cells.max(1);
+
+        // Sweeps grow about in step with the longer side. The share of exhaustive cells grows
+        // too, but levels off at around 20x20.
+        let passes_allowed = |passes_at_10: usize| (passes_at_10 * size.max(10)).div_ceil(10);
+        let exhaustive_allowed =
+            |permille_at_10: usize| permille_at_10 + size.clamp(10, 20).saturating_sub(10) * 5;
+
+        let passes = report.passes as usize;
+        let within = |passes_at_10, permille_at_10| {
+            passes <= passes_allowed(passes_at_10)
+                && exhaustive_permille <= exhaustive_allowed(permille_at_10)
+        };
+        if within(3, 10) {
+            Difficulty::Easy
+        } else if within(4, 40) {
+            Difficulty::Medium
+        } else if within(6, 70) {
+            Difficulty::Hard
+        } else {
+            Difficulty::Expert
+        }
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BoardLine {
+    Row(u8),
+    Column(u8),
 }
 
 enum BoardValidity {
     Valid,
-    Invalid(u8, u8),
+    // The first line whose filled cells don't match its clue.
+    Invalid(BoardLine),
 }
 
 #[derive(Clone)]
 pub struct GameState {
     pub settings: GameSettings,
     pub board_state: BoardState,
+    history: History,
+    // Lives left, in lives mode.
+    pub lives: Option<u8>,
+    // The mark being painted across the board, started by the latest fill, cross or clear.
+    stroke: Option<Stroke>,
+    // With the pen down, moving paints the stroke without holding shift.
+    pen_down: bool,
+    // Time played before this session of the game, and when this session started.
+    played: Duration,
+    resumed: Instant,
+    // Imported puzzles are named after their title or file, generated ones by their puzzle code.
+    pub puzzle_name: Option<String>,
+    // Shown in place of the check result until the next key press.
+    notice: Option<String>,
+    // Changes whenever the board or the history does, and is never shared by two games, so a
+    // copy of the game can tell whether it's out of date.
+    revision: u64,
+}
+
+// Where the next revision of any game is taken from.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(0);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+// Painting only changes cells that had the same value as the cell the stroke started on.
+#[derive(Clone, Copy)]
+struct Stroke {
+    from: Option<bool>,
+    to: Option<bool>,
 }
 
 #[derive(Clone)]
 pub struct BoardState {
-    size: u8,
+    width: u8,
+    height: u8,
     // Values generated at the start
     pub true_values: Vec<Vec<bool>>,
     row_counts: Vec<Vec<u8>>,
@@ -41,181 +228,771 @@ pub struct BoardState {
     // Values assigned by the player
     assigned_values: Vec<Vec<Option<bool>>>,
     selected_square: (u8, u8),
-    invalid_tile: Option<(u8, u8)>,
+    invalid_line: Option<BoardLine>,
+    // Cells marked wrongly at the last check, in row-major order.
+    mistakes: Vec<(u8, u8)>,
+    // How hard the board is to line solve, or None if line logic alone can't solve it.
+    rating: Option<Difficulty>,
+    // Whether the clues only have the one solution in `true_values`. If they have others, a cell
+    // that differs from it isn't necessarily a mistake.
+    unique: bool,
+}
+
+/// The part of the board that fits on screen.
+#[derive(Clone, Copy)]
+struct Viewport {
+    first_row: u8,
+    rows: u8,
+    first_column: u8,
+    columns: u8,
+}
+
+impl Viewport {
+    fn is_clipped(&self, board: &BoardState) -> bool {
+        self.rows < board.height || self.columns < board.width
+    }
+}
+
+/// How the clues are laid out around the grid. Every number in a clue gets the width of the widest
+/// one, so clues of 10 or more stay lined up with their row or column.
+#[derive(Clone, Copy)]
+struct ClueLayout {
+    // Terminal columns taken by each row clue number, and by each column clue number.
+    row_number_width: u16,
+    column_number_width: u16,
+    // Most numbers in any row clue, and in any column clue.
+    row_clue_len: u16,
+    column_clue_len: u16,
+    // Terminal columns the player asked each cell to take.
+    cell_width: u16,
+}
+
+impl ClueLayout {
+    fn new(row_counts: &[Vec<u8>], column_counts: &[Vec<u8>], cell_width: u8) -> Self {
+        let longest = |counts: &[Vec<u8>]| counts.iter().map(|c| c.len()).max().unwrap_or(0);
+        let widest = |counts: &[Vec<u8>]| {
+            counts
+                .iter()
+                .flatten()
+                .map(|v| v.to_string().len())
+                .max()
+                .unwrap_or(1)
+        };
+
+        ClueLayout {
+            row_number_width: widest(row_counts) as u16,
+            column_number_width: widest(column_counts) as u16,
+            row_clue_len: longest(row_counts) as u16,
+            column_clue_len: longest(column_counts) as u16,
+            cell_width: cell_width as u16,
+        }
+    }
+
+    /// Width of the row clues, with a space after each number.
+    fn row_clues_width(&self) -> u16 {
+        self.row_clue_len * (self.row_number_width + 1)
+    }
+
+    /// Height of the column clues, one number per line.
+    fn column_clues_height(&self) -> u16 {
+        self.column_clue_len
+    }
+
+    /// Terminal columns per board column. Single digit column clues sit side by side, wider ones
+    /// need a space between them, and cells can be asked to be wider still.
+    fn column_width(&self) -> u16 {
+        let clue_width = match self.column_number_width {
+            1 => 1,
+            width => width + 1,
+        };
+        clue_width.max(self.cell_width)
+    }
 }
 
+/// Where a window of `visible` cells out of `total` should start so that `selected` sits in the
+/// middle of it, without scrolling past either end.
+fn scroll_offset(selected: u8, visible: u8, total: u8) -> u8 {
+    selected.saturating_sub(visible / 2).min(total - visible)
+}
+
+/// The symbol a cell is drawn with: filled, crossed or unmarked.
+fn cell_char(value: Option<bool>) -> char {
+    match value {
+        Some(true) => '#',
+        Some(false) => 'X',
+        None => '.',
+    }
+}
+
+/// Formats a duration as minutes and seconds.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Background of every other 5x5 block of cells, to make counting easier.
+const GUIDE_SHADE: Color = Color::Indexed(236);
+// Background of the selected square's row and column when the crosshair is on.
+const CROSSHAIR_SHADE: Color = Color::Indexed(239);
+
 impl BoardState {
-    fn render(self) -> Paragraph<'static> {
-        let max_len_column_counts = self.column_counts.iter().map(|x| x.len()).max().unwrap();
-        let max_len_row_counts = self.row_counts.iter().map(|x| x.len()).max().unwrap();
+    /// Styles for the numbers of a row or column clue. The clues for the selected square's row
+    /// and column are highlighted, numbers matched by finished runs are dimmed, and the whole clue
+    /// is red when the line can no longer match it.
+    fn clue_styles(&self, line: BoardLine) -> Vec<Style> {
+        let (clue, values, selected) = match line {
+            BoardLine::Row(m) => (
+                &self.row_counts[m as usize],
+                self.assigned_values[m as usize].clone(),
+                m == self.selected_square.0,
+            ),
+            BoardLine::Column(n) => (
+                &self.column_counts[n as usize],
+                self.assigned_values
+                    .iter()
+                    .map(|row| row[n as usize])
+                    .collect(),
+                n == self.selected_square.1,
+            ),
+        };
 
-        let mut lines: Vec<Line> = Vec::new();
+        let base = match selected {
+            true => Style::new().yellow().bold(),
+            false => Style::new().white(),
+        };
+        if solver::solve_line(clue, &values).is_none() {
+            return vec![base.red(); clue.len()];
+        }
+        solver::satisfied_blocks(clue, &values)
+            .into_iter()
+            .map(|satisfied| match satisfied {
+                true => base.dark_gray(),
+                false => base,
+            })
+            .collect()
+    }
 
-        // Render column counts
-        let column_paddings: Vec<usize> = self
-            .column_counts
-            .iter()
-            .map(|x| max_len_column_counts - x.len())
-            .collect();
-        for j in 0..max_len_column_counts {
-            let mut column_counts_line: String =
-                String::from(" ".repeat((max_len_row_counts * 2) as usize));
-            for n in 0..self.size {
-                if j < column_paddings[n as usize] {
-                    column_counts_line.push(' ');
-                } else {
-                    let current_count =
-                        self.column_counts[n as usize].get(j - column_paddings[n as usize]);
-                    if let Some(v) = current_count {
-                        column_counts_line.push_str(v.to_string().as_str());
-                    } else {
-                        column_counts_line.push(' ');
-                    }
-                }
-            }
-            lines.push(Line::from(column_counts_line.white()))
-        }
-
-        for m in 0..self.size {
-            let mut line_characters: Vec<Span<'static>> = Vec::new();
-
-            // Render row counts
-            let row_padding = max_len_row_counts - self.row_counts[m as usize].len();
-            for i in 0..max_len_row_counts {
-                if i < row_padding {
-                    line_characters.push(" ".white());
-                } else {
-                    let current_count = self.row_counts[m as usize].get(i - row_padding);
-                    if let Some(v) = current_count {
-                        line_characters.push(v.to_string().white());
-                    }
+    /// Where the cursor is, and which part of the board is on screen if it doesn't all fit.
+    fn position_text(&self, viewport: Viewport) -> String {
+        let (m, n) = self.selected_square;
+        let mut text = format!(
+            "Row {}/{}, column {}/{}",
+            m + 1,
+            self.height,
+            n + 1,
+            self.width
+        );
+
+        if viewport.is_clipped(self) {
+            text.push_str(&format!(
+                " (showing rows {}-{}, columns {}-{})",
+                viewport.first_row + 1,
+                viewport.first_row + viewport.rows,
+                viewport.first_column + 1,
+                viewport.first_column + viewport.columns
+            ));
+        }
+        text
+    }
+
+    pub fn line_solve_report(&self) -> solver::LineSolveReport {
+        solver::line_solve(&self.row_counts, &self.column_counts)
+    }
+
+    /// Changes a cell the player has marked. Anything shown from the last check no longer
+    /// applies once the board changes.
+    fn set_assigned(&mut self, row: u8, column: u8, value: Option<bool>) {
+        self.assigned_values[row as usize][column as usize] = value;
+        self.invalid_line = None;
+        self.mistakes.clear();
+    }
+
+    /// Every filled cell that should be empty and every crossed cell that should be filled.
+    fn find_mistakes(&self) -> Vec<(u8, u8)> {
+        let mut mistakes = Vec::new();
+        for m in 0..self.height {
+            for n in 0..self.width {
+                let assigned = self.assigned_values[m as usize][n as usize];
+                if assigned.is_some_and(|v| v != self.true_values[m as usize][n as usize]) {
+                    mistakes.push((m, n));
                 }
-                line_characters.push(" ".white());
             }
+        }
+        mistakes
+    }
 
-            // Render board
-            for n in 0..self.size {
-                let value = self
-                    .assigned_values
-                    .get(m as usize)
-                    .unwrap()
-                    .get(n as usize)
-                    .unwrap();
-                let ch: char = if let Some(v) = value {
-                    match v {
-                        true => '#',
-                        false => 'X',
-                    }
-                } else {
-                    '.'
-                };
-
-                if (m, n) == self.selected_square {
-                    line_characters.push(ch.to_string().red())
-                } else {
-                    line_characters.push(ch.to_string().white())
-                }
+    /// What the last check found, for the status line. A puzzle with more than one solution has
+    /// no single answer to compare cells with, so only the lines that contradict their clues are
+    /// counted.
+    fn check_text(&self) -> Vec<Line<'static>> {
+        let mut lines = Vec::new();
+        match self.invalid_line {
+            Some(BoardLine::Row(m)) => {
+                lines.push(Line::from(format!("Row {} doesn't match its clue.", m + 1)))
             }
-            lines.push(Line::from(line_characters));
+            Some(BoardLine::Column(n)) => lines.push(Line::from(format!(
+                "Column {} doesn't match its clue.",
+                n + 1
+            ))),
+            None => return lines,
         }
 
-        Paragraph::new(Text::from(lines))
+        if !self.unique {
+            lines.push(Line::from(match self.contradicting_lines().len() {
+                0 => String::from("No line contradicts its clue so far."),
+                1 => String::from("1 line contradicts its clue, shown in red."),
+                n => format!("{} lines contradict their clues, shown in red.", n),
+            }));
+            return lines;
+        }
+
+        let wrong_fills = self
+            .mistakes
+            .iter()
+            .filter(|(m, n)| self.assigned_values[*m as usize][*n as usize] == Some(true))
+            .count();
+        let wrong_crosses = self.mistakes.len() - wrong_fills;
+        lines.push(Line::from(match self.mistakes.is_empty() {
+            true => String::from("No mistakes so far."),
+            false => format!(
+                "{} wrong fills, {} wrong crosses. Esc clears the highlights, f fixes them.",
+                wrong_fills, wrong_crosses
+            ),
+        }));
+        lines
+    }
+
+    /// The rows and columns that can no longer be completed to match their clue, with crossed
+    /// cells counting as empty.
+    fn contradicting_lines(&self) -> Vec<BoardLine> {
+        let rows = (0..self.height).filter(|m| {
+            let row = &self.assigned_values[*m as usize];
+            solver::solve_line(&self.row_counts[*m as usize], row).is_none()
+        });
+        let columns = (0..self.width).filter(|n| {
+            let column: Vec<Option<bool>> = self
+                .assigned_values
+                .iter()
+                .map(|row| row[*n as usize])
+                .collect();
+            solver::solve_line(&self.column_counts[*n as usize], &column).is_none()
+        });
+        rows.map(BoardLine::Row)
+            .chain(columns.map(BoardLine::Column))
+            .collect()
+    }
+
+    /// True if every row and column could still be completed to match its clue.
+    fn is_consistent(&self) -> bool {
+        self.contradicting_lines().is_empty()
     }
 
+    /// Checks the filled cells against the clues. Unmarked and crossed cells both count as
+    /// empty, so any grid that reproduces every clue wins, not just the one the board was
+    /// generated from.
     fn check_assigned(&self) -> BoardValidity {
-        for m in 0..self.size {
-            for n in 0..self.size {
-                let assigned_value = self
-                    .assigned_values
-                    .get(m as usize)
-                    .unwrap()
-                    .get(n as usize)
-                    .unwrap();
-
-                let true_value = self
-                    .true_values
-                    .get(m as usize)
-                    .unwrap()
-                    .get(n as usize)
-                    .unwrap();
-
-                match (assigned_value, true_value) {
-                    (Some(v1), v2) => {
-                        if v1 != v2 {
-                            return BoardValidity::Invalid(m, n);
-                        }
-                    }
-                    (None, _) => return BoardValidity::Invalid(m, n),
+        let filled: Vec<Vec<bool>> = self
+            .assigned_values
+            .iter()
+            .map(|row| row.iter().map(|c| *c == Some(true)).collect())
+            .collect();
+        let (row_counts, column_counts) = counts_from_values(&filled);
+
+        if let Some(m) =
+            (0..self.height).find(|m| row_counts[*m as usize] != self.row_counts[*m as usize])
+        {
+            return BoardValidity::Invalid(BoardLine::Row(m));
+        }
+        if let Some(n) =
+            (0..self.width).find(|n| column_counts[*n as usize] != self.column_counts[*n as usize])
+        {
+            return BoardValidity::Invalid(BoardLine::Column(n));
+        }
+
+        BoardValidity::Valid
+    }
+}
+
+// How many cells the generator may flip in a board before giving up on it.
+const MAX_REPAIRS: usize = 200;
+// How much denser the generator aims after each board it gave up on.
+const DENSITY_RELAXATION: f64 = 0.05;
+// How much solving the generator may do while looking for the requested difficulty, in cells
+// swept. Boards are generated while the player waits, so this keeps it to about half a second,
+// and big boards get fewer attempts than small ones.
+const GENERATION_BUDGET: usize = 300_000;
+
+/// Turns each row and column of a solution into its clue: the lengths of its runs of filled cells.
+pub fn counts_from_values(true_values: &[Vec<bool>]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+    let line_counts = |line: &mut dyn Iterator<Item = bool>| {
+        let mut counts: Vec<u8> = Vec::new();
+        let mut reset_counts = true;
+        for b in line {
+            if b {
+                if reset_counts {
+                    counts.push(0);
                 }
+                *counts.last_mut().unwrap() += 1;
+                reset_counts = false;
+            } else {
+                reset_counts = true;
             }
         }
+        counts
+    };
+
+    let row_counts = true_values
+        .iter()
+        .map(|row| line_counts(&mut row.iter().copied()))
+        .collect();
+    let width = true_values.first().map_or(0, |row| row.len());
+    let column_counts = (0..width)
+        .map(|n| line_counts(&mut true_values.iter().map(|row| row[n])))
+        .collect();
 
-        return BoardValidity::Valid;
+    (row_counts, column_counts)
+}
+
+/// The sparsest board the generator aims for at a size. The sparser a board, the less line logic
+/// has to go on, and the bigger the board the more that matters, so below this the repairs rarely
+/// settle.
+pub fn min_density(width: u8, height: u8) -> f64 {
+    let size = width.max(height) as f64;
+    (0.3 + (size - 20.0) * 0.02).clamp(0.3, 0.6)
+}
+
+/// The difficulties the generator can be expected to find at a size and density. Dense boards
+/// leave no room for hard deductions and sparse ones no room for easy ones, and the bigger the
+/// board the sooner that shows.
+pub fn reachable_difficulties(width: u8, height: u8, density: f64) -> RangeInclusive<Difficulty> {
+    let size = width.max(height);
+    let percent = (density * 100.0).round() as u8;
+    let easiest = match size >= 12 && percent <= 40 {
+        true => Difficulty::Medium,
+        false => Difficulty::Easy,
+    };
+    let hardest = if percent >= 80 || (size >= 12 && percent >= 70) {
+        Difficulty::Easy
+    } else if size >= 15 && percent >= 60 {
+        Difficulty::Medium
+    } else {
+        Difficulty::Expert
+    };
+    easiest..=hardest
+}
+
+/// Rolls a random board, then repairs it until line logic alone can solve it. Such a board has
+/// exactly one solution and never needs guessing. Wherever the solver stalls, a few of the cells it
+/// couldn't decide get flipped, which changes the clues around them. Boards that don't settle
+/// after a while are thrown away, and the next one aims a little denser. Every cell the solver
+/// sweeps is added to `work`.
+fn generate_solvable(
+    settings: &GameSettings,
+    rng: &mut impl Rng,
+    work: &mut usize,
+) -> (Vec<Vec<bool>>, solver::LineSolveReport) {
+    let cells = settings.width as usize * settings.height as usize;
+    let mut aim = settings
+        .density
+        .max(min_density(settings.width, settings.height));
+    'generate: loop {
+        let mut true_values: Vec<Vec<bool>> = (0..settings.height)
+            .map(|_| (0..settings.width).map(|_| rng.gen_bool(aim)).collect())
+            .collect();
+
+        for _ in 0..MAX_REPAIRS {
+            let (row_counts, column_counts) = counts_from_values(&true_values);
+            let report = solver::line_solve(&row_counts, &column_counts);
+            // The solver sweeps the board once more than it has passes, to find nothing new.
+            *work += (report.passes as usize + 1) * cells;
+            if report.is_solved() {
+                return (true_values, report);
+            }
+            if report.contradiction {
+                continue 'generate;
+            }
+
+            let undecided: Vec<(usize, usize)> = report
+                .grid
+                .iter()
+                .enumerate()
+                .flat_map(|(m, row)| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|(_, c)| c.is_none())
+                        .map(move |(n, _)| (m, n))
+                })
+                .collect();
+
+            // Fill cells in while the board is sparser than the aim and clear them out while it's
+            // denser, so the repairs don't pull it away from the requested density.
+            let fill = filled_share(&true_values) < aim;
+            let flippable: Vec<(usize, usize)> = undecided
+                .iter()
+                .copied()
+                .filter(|(m, n)| true_values[*m][*n] != fill)
+                .collect();
+            let candidates = if flippable.is_empty() {
+                &undecided
+            } else {
+                &flippable
+            };
+            for _ in 0..(candidates.len() / 8).max(1) {
+                let (m, n) = candidates[rng.gen_range(0..candidates.len())];
+                true_values[m][n] = !true_values[m][n];
+            }
+        }
+
+        aim = (aim + DENSITY_RELAXATION).min(1.0);
     }
 }
 
+/// The share of a board's cells that are filled.
+fn filled_share(true_values: &[Vec<bool>]) -> f64 {
+    let cells = true_values.iter().map(|row| row.len()).sum::<usize>();
+    let filled = true_values.iter().flatten().filter(|v| **v).count();
+    filled as f64 / cells.max(1) as f64
+}
+
+/// Rates the board the clues describe, if line logic alone can solve it.
+fn rate_board(row_counts: &[Vec<u8>], column_counts: &[Vec<u8>]) -> Option<Difficulty> {
+    let report = solver::line_solve(row_counts, column_counts);
+    report.is_solved().then(|| Difficulty::rate(&report))
+}
+
+/// Whether the clues have a single solution. Clues that line logic alone solves, and so have a
+/// rating, always do, which saves searching for a second one.
+fn has_unique_solution(
+    row_counts: &[Vec<u8>],
+    column_counts: &[Vec<u8>],
+    rating: Option<Difficulty>,
+) -> bool {
+    let unknown = vec![vec![None; column_counts.len()]; row_counts.len()];
+    rating.is_some()
+        || solver::count_solutions(row_counts, column_counts, unknown, 2).uniqueness()
+            == solver::Uniqueness::Unique
+}
+
 impl From<GameSettings> for BoardState {
     fn from(settings: GameSettings) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut true_values: Vec<Vec<bool>> = Vec::new();
-
-        let mut row_counts: Vec<Vec<u8>> = vec![Vec::new(); settings.size as usize];
-        let mut column_counts: Vec<Vec<u8>> = vec![Vec::new(); settings.size as usize];
-        let mut reset_row_counts: bool = true;
-        let mut reset_column_counts: Vec<bool> = vec![true; settings.size as usize];
-
-        let mut assigned_values: Vec<Vec<Option<bool>>> = Vec::new();
-        for m in 0..settings.size {
-            reset_row_counts = true;
-            let mut true_current_row: Vec<bool> = Vec::new();
-            let mut assigned_current_row: Vec<Option<bool>> = Vec::new();
-            for n in 0..settings.size {
-                let b: bool = rng.gen();
-                true_current_row.push(b);
-
-                // Swap comments here for debugging purposes.
-                // assigned_current_row.push(Some(b));
-                assigned_current_row.push(None);
-
-                if b {
-                    if reset_row_counts {
-                        row_counts[m as usize].push(0);
-                    }
-                    *row_counts[m as usize].last_mut().unwrap() += 1;
-
-                    if reset_column_counts[n as usize] {
-                        column_counts[n as usize].push(0);
-                    }
-                    *column_counts[n as usize].last_mut().unwrap() += 1;
-
-                    reset_row_counts = false;
-                    reset_column_counts[n as usize] = false;
-                } else {
-                    reset_row_counts = true;
-                    reset_column_counts[n as usize] = true;
-                }
+        // ChaCha rather than StdRng, since StdRng may change between versions of rand and a seed
+        // should keep naming the same puzzle.
+        let mut rng = ChaCha8Rng::seed_from_u64(settings.seed);
+
+        // Keep generating until a board matches the requested difficulty. Not every difficulty is
+        // reachable at every size, so settle for the closest board seen if none match in time. The
+        // board keeps its own rating, so the player can be told what they got instead.
+        let mut closest: Option<(Vec<Vec<bool>>, Difficulty)> = None;
+        let distance = |d: Difficulty| (d as u8).abs_diff(settings.difficulty as u8);
+        let mut work = 0;
+        while work < GENERATION_BUDGET {
+            let (true_values, report) = generate_solvable(&settings, &mut rng, &mut work);
+            let difficulty = Difficulty::rate(&report);
+
+            if closest
+                .as_ref()
+                .is_none_or(|(_, d)| distance(difficulty) < distance(*d))
+            {
+                closest = Some((true_values, difficulty));
+            }
+            if difficulty == settings.difficulty {
+                break;
             }
-            true_values.push(true_current_row);
-            assigned_values.push(assigned_current_row);
         }
+        let (true_values, rating) = closest.unwrap();
+        let (row_counts, column_counts) = counts_from_values(&true_values);
+
+        debug_assert_eq!(
+            solver::count_solutions(
+                &row_counts,
+                &column_counts,
+                vec![vec![None; settings.width as usize]; settings.height as usize],
+                2,
+            )
+            .uniqueness(),
+            solver::Uniqueness::Unique
+        );
+
+        // Swap comments here for debugging purposes.
+        // let assigned_values = true_values.iter().map(|row| row.iter().map(|b| Some(*b)).collect()).collect();
+        let assigned_values = vec![vec![None; settings.width as usize]; settings.height as usize];
 
         BoardState {
-            size: settings.size,
+            width: settings.width,
+            height: settings.height,
             true_values,
             row_counts,
             column_counts,
             assigned_values,
             selected_square: (0, 0),
-            invalid_tile: None,
+            invalid_line: None,
+            mistakes: Vec::new(),
+            rating: Some(rating),
+            unique: true,
+        }
+    }
+}
+
+impl TryFrom<Puzzle> for BoardState {
+    type Error = String;
+
+    /// Builds a board from imported clues. A puzzle without a solution is solved here, and if its
+    /// clues allow more than one solution, any of them is taken, since winning only goes by the
+    /// clues. The board remembers whether there were others, so the check doesn't hold the player
+    /// to the one taken.
+    fn try_from(puzzle: Puzzle) -> Result<Self, Self::Error> {
+        let Puzzle {
+            row_counts,
+            column_counts,
+            solution,
+            ..
+        } = puzzle;
+
+        let height = u8::try_from(row_counts.len())
+            .ok()
+            .filter(|h| *h > 0)
+            .ok_or("the puzzle should have from 1 to 255 rows")?;
+        let width = u8::try_from(column_counts.len())
+            .ok()
+            .filter(|w| *w > 0)
+            .ok_or("the puzzle should have from 1 to 255 columns")?;
+
+        let rating = rate_board(&row_counts, &column_counts);
+        let (true_values, unique) = match solution {
+            Some(solution) => {
+                if counts_from_values(&solution) != (row_counts.clone(), column_counts.clone()) {
+                    return Err(String::from("the solution doesn't match the clues"));
+                }
+                let unique = has_unique_solution(&row_counts, &column_counts, rating);
+                (solution, unique)
+            }
+            None => {
+                // Searching for a second solution tells whether the first is the only one.
+                let unknown = vec![vec![None; width as usize]; height as usize];
+                let search = solver::count_solutions(&row_counts, &column_counts, unknown, 2);
+                let unique = search.uniqueness() == solver::Uniqueness::Unique;
+                let solution = search.solutions.into_iter().next();
+                (solution.ok_or("the clues have no solution")?, unique)
+            }
+        };
+
+        Ok(BoardState {
+            width,
+            height,
+            true_values,
+            row_counts,
+            column_counts,
+            assigned_values: vec![vec![None; width as usize]; height as usize],
+            selected_square: (0, 0),
+            invalid_line: None,
+            mistakes: Vec::new(),
+            rating,
+            unique,
+        })
+    }
+}
+
+/// Draws the part of the board around the selected square that fits, with the clues for the
+/// visible rows and columns pinned to the top and left, and scrollbars for whatever is cut off.
+#[derive(Clone, Copy)]
+struct BoardWidget<'a> {
+    board: &'a BoardState,
+    clues: ClueLayout,
+    crosshair: bool,
+}
+
+impl<'a> BoardWidget<'a> {
+    fn new(board: &'a BoardState, settings: &GameSettings) -> Self {
+        BoardWidget {
+            board,
+            clues: ClueLayout::new(&board.row_counts, &board.column_counts, settings.cell_width),
+            crosshair: settings.crosshair,
+        }
+    }
+
+    fn viewport(&self, area: Rect) -> Viewport {
+        let board = self.board;
+        let clues = self.clues;
+
+        // Leave a cell to each side of the grid for the scrollbars.
+        let columns = (area.width.saturating_sub(clues.row_clues_width() + 1)
+            / clues.column_width())
+        .clamp(1, board.width as u16) as u8;
+        let rows = area
+            .height
+            .saturating_sub(clues.column_clues_height() + 1)
+            .clamp(1, board.height as u16) as u8;
+
+        Viewport {
+            first_row: scroll_offset(board.selected_square.0, rows, board.height),
+            rows,
+            first_column: scroll_offset(board.selected_square.1, columns, board.width),
+            columns,
+        }
+    }
+
+    /// The square drawn at a terminal cell when the widget is rendered into `area`.
+    fn square_at(&self, area: Rect, column: u16, row: u16) -> Option<(u8, u8)> {
+        let clues = self.clues;
+        let viewport = self.viewport(area);
+
+        let x = column.checked_sub(area.x + clues.row_clues_width())? / clues.column_width();
+        let y = row.checked_sub(area.y + clues.column_clues_height())?;
+        if x >= viewport.columns as u16 || y >= viewport.rows as u16 {
+            return None;
+        }
+        Some((
+            viewport.first_row + y as u8,
+            viewport.first_column + x as u8,
+        ))
+    }
+
+    fn cell_style(&self, m: u8, n: u8) -> Style {
+        let board = self.board;
+        let (selected_row, selected_column) = board.selected_square;
+
+        let style = match (m, n) == board.selected_square {
+            true => Style::new().red(),
+            false => Style::new().white(),
+        };
+        if board.mistakes.binary_search(&(m, n)).is_ok() {
+            style.on_light_red()
+        } else if self.crosshair && (m == selected_row || n == selected_column) {
+            style.bg(CROSSHAIR_SHADE)
+        } else if (m / 5 + n / 5) % 2 == 1 {
+            style.bg(GUIDE_SHADE)
+        } else {
+            style
+        }
+    }
+}
+
+impl Widget for BoardWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let board = self.board;
+        let clues = self.clues;
+        let (clue_width, clue_height) = (clues.row_clues_width(), clues.column_clues_height());
+        let column_width = clues.column_width();
+        let row_number_width = clues.row_number_width as usize;
+
+        let viewport = self.viewport(area);
+        let grid_x = area.x + clue_width;
+        let grid_y = area.y + clue_height;
+        let grid_width = viewport.columns as u16 * column_width;
+
+        // Anything that doesn't fit in the area is cut off.
+        let mut draw = |x: u16, y: u16, text: String, style: Style| {
+            if x < area.right() && y < area.bottom() {
+                buf.set_stringn(x, y, text, (area.right() - x) as usize, style);
+            }
+        };
+
+        for (i, n) in (viewport.first_column..viewport.first_column + viewport.columns).enumerate()
+        {
+            let x = grid_x + i as u16 * column_width;
+
+            // Column clues are lined up at the bottom, so every clue ends just above the grid.
+            let counts = &board.column_counts[n as usize];
+            let padding = clues.column_clue_len - counts.len() as u16;
+            for (k, (v, style)) in counts
+                .iter()
+                .zip(board.clue_styles(BoardLine::Column(n)))
+                .enumerate()
+            {
+                let text = format!("{:>1$}", v, column_width as usize);
+                draw(x, area.y + padding + k as u16, text, style);
+            }
+        }
+
+        for (j, m) in (viewport.first_row..viewport.first_row + viewport.rows).enumerate() {
+            let y = grid_y + j as u16;
+
+            // Row clues are lined up on the right, against the grid.
+            let counts = &board.row_counts[m as usize];
+            let padding = clues.row_clue_len - counts.len() as u16;
+            for (k, (v, style)) in counts
+                .iter()
+                .zip(board.clue_styles(BoardLine::Row(m)))
+                .enumerate()
+            {
+                let x = area.x + (padding + k as u16) * (row_number_width as u16 + 1);
+                draw(x, y, format!("{:>1$}", v, row_number_width), style);
+            }
+
+            for (i, n) in
+                (viewport.first_column..viewport.first_column + viewport.columns).enumerate()
+            {
+                let ch = cell_char(board.assigned_values[m as usize][n as usize]);
+                let x = grid_x + i as u16 * column_width;
+                let text = format!("{:>1$}", ch, column_width as usize);
+                draw(x, y, text, self.cell_style(m, n));
+            }
+        }
+
+        if viewport.rows < board.height {
+            let scrollbar_area = Rect {
+                x: grid_x + grid_width,
+                y: grid_y,
+                width: 1,
+                height: viewport.rows as u16,
+            };
+            let mut scrollbar_state =
+                ScrollbarState::new((board.height - viewport.rows) as usize + 1)
+                    .position(viewport.first_row as usize)
+                    .viewport_content_length(viewport.rows as usize);
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .render(scrollbar_area.intersection(area), buf, &mut scrollbar_state);
+        }
+
+        if viewport.columns < board.width {
+            let scrollbar_area = Rect {
+                x: grid_x,
+                y: grid_y + viewport.rows as u16,
+                width: grid_width,
+                height: 1,
+            };
+            let mut scrollbar_state =
+                ScrollbarState::new((board.width - viewport.columns) as usize + 1)
+                    .position(viewport.first_column as usize)
+                    .viewport_content_length(viewport.columns as usize);
+            Scrollbar::new(ScrollbarOrientation::HorizontalBottom)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .render(scrollbar_area.intersection(area), buf, &mut scrollbar_state);
         }
     }
 }
 
 impl GameState {
-    fn toggle_selected_square(&mut self) -> color_eyre::Result<ScreenMessage> {
+    /// Sets cells of the board as a single edit that can be undone in one go.
+    fn edit_cells(&mut self, cells: &[(u8, u8, Option<bool>)]) {
+        let mut changes: Vec<CellChange> = Vec::new();
+        for (row, column, value) in cells.iter().copied() {
+            let before = self.board_state.assigned_values[row as usize][column as usize];
+            if before != value {
+                changes.push(CellChange {
+                    row,
+                    column,
+                    before,
+                    after: value,
+                });
+                self.board_state.set_assigned(row, column, value);
+            }
+        }
+        if !changes.is_empty() {
+            self.revision = next_revision();
+        }
+        self.history.record(changes);
+    }
+
+    /// Marks the selected square as filled, crossed or empty. Marking a square that already has
+    /// that mark empties it again.
+    fn mark_selected_square(&mut self, mark: Option<bool>) -> color_eyre::Result<ScreenMessage> {
         let selected_square = self.board_state.selected_square;
 
-        let current_state = self
+        let current_state = *self
             .board_state
             .assigned_values
             .get(selected_square.0 as usize)
@@ -223,22 +1000,188 @@ impl GameState {
             .get(selected_square.1 as usize)
             .unwrap();
 
-        let new_state = match current_state {
-            None => Some(true),
-            Some(true) => Some(false),
-            Some(false) => None,
+        let new_state = match current_state == mark {
+            true => None,
+            false => mark,
         };
 
-        let mut current_row = self
-            .board_state
-            .assigned_values
-            .get(selected_square.0 as usize)
-            .unwrap()
-            .clone();
+        self.end_stroke();
+        let message = self.assign_selected_square(new_state)?;
+        if current_state != new_state {
+            // The rest of the stroke is undone together with this first cell.
+            self.stroke = Some(Stroke {
+                from: current_state,
+                to: new_state,
+            });
+            self.history.join_next();
+        }
+        Ok(message)
+    }
+
+    /// Carries the current stroke on to the selected square, if it has the value the stroke
+    /// started on.
+    fn paint_selected_square(&mut self) -> color_eyre::Result<ScreenMessage> {
+        let Some(stroke) = self.stroke else {
+            return Ok(ScreenMessage::Noop);
+        };
+
+        let (row, column) = self.board_state.selected_square;
+        if self.board_state.assigned_values[row as usize][column as usize] != stroke.from {
+            return Ok(ScreenMessage::Noop);
+        }
+        self.assign_selected_square(stroke.to)
+    }
+
+    fn board_widget(&self) -> BoardWidget<'_> {
+        BoardWidget::new(&self.board_state, &self.settings)
+    }
+
+    /// Time spent on the game, across every session it was played in.
+    pub fn elapsed(&self) -> Duration {
+        self.played + self.resumed.elapsed()
+    }
+
+    /// Which puzzle this is: its name if it was imported, or the code it was generated from.
+    pub fn puzzle_text(&self) -> String {
+        match &self.puzzle_name {
+            Some(name) => format!("Puzzle: {}", name),
+            None => format!("Puzzle: {}", self.settings.puzzle_code()),
+        }
+    }
+
+    fn status_text(&self) -> String {
+        let mut status = format!(
+            "{}  Time: {}",
+            self.puzzle_text(),
+            format_duration(self.elapsed())
+        );
+        if let Some(rating) = self.board_state.rating {
+            if self.puzzle_name.is_none() && rating != self.settings.difficulty {
+                status.push_str(&format!(
+                    "  No {} board turned up, this one is {}",
+                    self.settings.difficulty, rating
+                ));
+            }
+        }
+        if let Some(lives) = self.lives {
+            status.push_str(&format!("  Lives: {}/{}", lives, STARTING_LIVES));
+        }
+        if self.pen_down {
+            status.push_str("  Pen down");
+        }
+        status
+    }
+
+    /// Left click fills and right click crosses the clicked square, and dragging paints the
+    /// stroke that started.
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> color_eyre::Result<ScreenMessage> {
+        if let MouseEventKind::Up(_) = mouse.kind {
+            self.end_stroke();
+            return Ok(ScreenMessage::Noop);
+        }
+
+        let board_area = game_layout(terminal::screen_area()?)[0];
+        let Some(square) = self
+            .board_widget()
+            .square_at(board_area, mouse.column, mouse.row)
+        else {
+            return Ok(ScreenMessage::Noop);
+        };
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.board_state.selected_square = square;
+                self.mark_selected_square(Some(true))
+            }
+            MouseEventKind::Down(MouseButton::Right) => {
+                self.board_state.selected_square = square;
+                self.mark_selected_square(Some(false))
+            }
+            MouseEventKind::Drag(_) if square != self.board_state.selected_square => {
+                self.board_state.selected_square = square;
+                self.paint_selected_square()
+            }
+            _ => Ok(ScreenMessage::Noop),
+        }
+    }
+
+    fn end_stroke(&mut self) {
+        self.stroke = None;
+        self.history.stop_joining();
+    }
+
+    fn toggle_pen(&mut self) -> color_eyre::Result<ScreenMessage> {
+        self.pen_down = !self.pen_down;
+        if !self.pen_down {
+            self.end_stroke();
+        }
+        Ok(ScreenMessage::Noop)
+    }
 
-        current_row[selected_square.1 as usize] = new_state;
+    fn assign_selected_square(&mut self, value: Option<bool>) -> color_eyre::Result<ScreenMessage> {
+        let (row, column) = self.board_state.selected_square;
+        if value == Some(true) && self.lives.is_some() {
+            return self.fill_checked(row, column);
+        }
+
+        self.edit_cells(&[(row, column, value)]);
+        Ok(ScreenMessage::Noop)
+    }
+
+    /// Fills a cell in lives mode. A cell that should be empty is crossed instead, and costs a
+    /// life. Running out of lives ends the game.
+    fn fill_checked(&mut self, row: u8, column: u8) -> color_eyre::Result<ScreenMessage> {
+        if self.board_state.true_values[row as usize][column as usize] {
+            self.edit_cells(&[(row, column, Some(true))]);
+            return Ok(ScreenMessage::Noop);
+        }
+
+        self.edit_cells(&[(row, column, Some(false))]);
+        let lives = self.lives.map(|l| l.saturating_sub(1));
+        self.lives = lives;
+        match lives {
+            Some(0) => Ok(ScreenMessage::ChangeScreen(Screen::End(EndState::from(
+                self.clone(),
+            )))),
+            _ => Ok(ScreenMessage::Noop),
+        }
+    }
+
+    /// Puts back the board as it was before the latest edit. Returns false if there was nothing
+    /// left to undo.
+    fn undo_edit(&mut self) -> bool {
+        let Some(changes) = self.history.undo() else {
+            return false;
+        };
+        self.revision = next_revision();
+        for change in changes.iter().rev() {
+            self.board_state
+                .set_assigned(change.row, change.column, change.before);
+            self.board_state.selected_square = (change.row, change.column);
+        }
+        true
+    }
 
-        self.board_state.assigned_values[selected_square.0 as usize] = current_row;
+    fn undo(&mut self) -> color_eyre::Result<ScreenMessage> {
+        self.undo_edit();
+        Ok(ScreenMessage::Noop)
+    }
+
+    fn redo(&mut self) -> color_eyre::Result<ScreenMessage> {
+        if let Some(changes) = self.history.redo() {
+            self.revision = next_revision();
+            for change in changes {
+                self.board_state
+                    .set_assigned(change.row, change.column, change.after);
+                self.board_state.selected_square = (change.row, change.column);
+            }
+        }
+        Ok(ScreenMessage::Noop)
+    }
+
+    /// Undoes edits until no row or column contradicts its clue any more.
+    fn undo_to_consistent(&mut self) -> color_eyre::Result<ScreenMessage> {
+        while !self.board_state.is_consistent() && self.undo_edit() {}
         Ok(ScreenMessage::Noop)
     }
 
@@ -250,7 +1193,7 @@ impl GameState {
 
     fn move_selected_down(&mut self) -> color_eyre::Result<ScreenMessage> {
         let selected_square = self.board_state.selected_square;
-        if selected_square.0 == (self.board_state.size - 1) as u8 {
+        if selected_square.0 == self.board_state.height - 1 {
             return Ok(ScreenMessage::Noop);
         }
         self.board_state.selected_square = (selected_square.0.saturating_add(1), selected_square.1);
@@ -265,13 +1208,33 @@ impl GameState {
 
     fn move_selected_right(&mut self) -> color_eyre::Result<ScreenMessage> {
         let selected_square = self.board_state.selected_square;
-        if selected_square.1 == (self.board_state.size - 1) as u8 {
+        if selected_square.1 == self.board_state.width - 1 {
             return Ok(ScreenMessage::Noop);
         }
         self.board_state.selected_square = (selected_square.0, selected_square.1.saturating_add(1));
         Ok(ScreenMessage::Noop)
     }
 
+    /// Writes the puzzle to a `.non` file in the data directory.
+    fn export_puzzle(&mut self) -> color_eyre::Result<ScreenMessage> {
+        let text = formats::non::export(&self.to_puzzle());
+        self.export("puzzle", EXPORT_FILE, &text)
+    }
+
+    fn export_board(&mut self) -> color_eyre::Result<ScreenMessage> {
+        let text = formats::ascii::export(&self.board_state.assigned_values);
+        self.export("board", BOARD_EXPORT_FILE, &text)
+    }
+
+    fn export(&mut self, what: &str, name: &str, text: &str) -> color_eyre::Result<ScreenMessage> {
+        let written = storage::write(name, text).and_then(|_| storage::path(name));
+        self.notice = Some(match written {
+            Ok(path) => format!("Exported the {} to {}", what, path.display()),
+            Err(error) => format!("Couldn't export the {}: {}", what, error),
+        });
+        Ok(ScreenMessage::Noop)
+    }
+
     fn check_assigned(&mut self) -> color_eyre::Result<ScreenMessage> {
         let solved = self.board_state.check_assigned();
 
@@ -279,33 +1242,150 @@ impl GameState {
             BoardValidity::Valid => Ok(ScreenMessage::ChangeScreen(Screen::End(EndState::from(
                 self.clone(),
             )))),
-            BoardValidity::Invalid(m, n) => {
-                self.board_state.invalid_tile = Some((m, n));
+            BoardValidity::Invalid(line) => {
+                self.board_state.invalid_line = Some(line);
+                if self.board_state.unique {
+                    self.board_state.mistakes = self.board_state.find_mistakes();
+                }
                 Ok(ScreenMessage::Noop)
             }
         }
     }
+
+    fn clear_mistakes(&mut self) -> color_eyre::Result<ScreenMessage> {
+        self.board_state.invalid_line = None;
+        self.board_state.mistakes.clear();
+        Ok(ScreenMessage::Noop)
+    }
+
+    /// Corrects every mistake found by the last check, as a single edit. A puzzle with more than
+    /// one solution has nothing to correct the board to.
+    fn fix_mistakes(&mut self) -> color_eyre::Result<ScreenMessage> {
+        if !self.board_state.unique {
+            self.notice = Some(String::from(
+                "This puzzle has more than one solution, so there's no single answer to fix to.",
+            ));
+            return Ok(ScreenMessage::Noop);
+        }
+
+        let fixes: Vec<(u8, u8, Option<bool>)> = self
+            .board_state
+            .mistakes
+            .iter()
+            .map(|(m, n)| {
+                let value = self.board_state.true_values[*m as usize][*n as usize];
+                (*m, *n, Some(value))
+            })
+            .collect();
+        self.edit_cells(&fixes);
+        Ok(ScreenMessage::Noop)
+    }
+
+    /// Ends the game as soon as the board is solved, unless the player has asked to check it
+    /// themselves.
+    fn detect_win(&mut self) -> color_eyre::Result<ScreenMessage> {
+        if self.settings.strict_check {
+            return Ok(ScreenMessage::Noop);
+        }
+
+        match self.board_state.check_assigned() {
+            BoardValidity::Valid => Ok(ScreenMessage::ChangeScreen(Screen::End(EndState::from(
+                self.clone(),
+            )))),
+            BoardValidity::Invalid(_) => Ok(ScreenMessage::Noop),
+        }
+    }
 }
 
-impl Default for GameState {
-    fn default() -> Self {
-        let default_settings = GameSettings { size: 10 };
+impl GameState {
+    /// Starts a game of an imported puzzle.
+    pub fn from_puzzle(puzzle: Puzzle) -> Result<Self, String> {
+        // The name is saved on a single line, so any line breaks in it become spaces.
+        let puzzle_name = puzzle
+            .name
+            .as_deref()
+            .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "));
+        let board_state = BoardState::try_from(puzzle)?;
+
+        let settings = GameSettings {
+            width: board_state.width,
+            height: board_state.height,
+            ..GameSettings::default()
+        };
+        let mut game_state = GameState::new(settings, board_state);
+        game_state.puzzle_name = puzzle_name;
+        Ok(game_state)
+    }
+
+    /// Starts a game on a board that has already been built.
+    fn new(settings: GameSettings, board_state: BoardState) -> Self {
         GameState {
-            settings: default_settings,
-            board_state: BoardState::from(default_settings),
+            settings,
+            board_state,
+            history: History::default(),
+            lives: match settings.mode {
+                GameMode::Classic => None,
+                GameMode::Lives => Some(STARTING_LIVES),
+            },
+            stroke: None,
+            pen_down: false,
+            played: Duration::ZERO,
+            resumed: Instant::now(),
+            puzzle_name: None,
+            notice: None,
+            revision: next_revision(),
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn to_puzzle(&self) -> Puzzle {
+        let board = &self.board_state;
+        Puzzle {
+            name: self.puzzle_name.clone(),
+            row_counts: board.row_counts.clone(),
+            column_counts: board.column_counts.clone(),
+            solution: Some(board.true_values.clone()),
         }
     }
 }
 
+impl Default for GameState {
+    fn default() -> Self {
+        GameState::from(GameSettings::default())
+    }
+}
+
 impl From<GameSettings> for GameState {
     fn from(settings: GameSettings) -> Self {
-        GameState {
-            settings,
-            board_state: BoardState::from(settings),
-        }
+        GameState::new(settings, BoardState::from(settings))
     }
 }
 
+/// Shown while a board is generated, which can take a moment for big boards.
+pub fn render_generating(frame: &mut Frame, settings: &GameSettings) {
+    let text = format!(
+        "Generating a {}x{} {} board...",
+        settings.width, settings.height, settings.difficulty
+    );
+    frame.render_widget(Paragraph::new(text), frame.size());
+}
+
+/// The board, then lines for the position, the check result and the status.
+fn game_layout(area: Rect) -> Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(vec![
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(2),
+            Constraint::Length(1),
+        ])
+        .split(area)
+}
+
 impl EventHandler for GameState {
     fn handle_events(
         &mut self,
@@ -316,14 +1396,54 @@ impl EventHandler for GameState {
                 return Ok(ScreenMessage::Noop);
             }
 
-            return match key.code {
-                KeyCode::Char(' ') => self.toggle_selected_square(),
-                KeyCode::Up => self.move_selected_up(),
-                KeyCode::Down => self.move_selected_down(),
-                KeyCode::Left => self.move_selected_left(),
-                KeyCode::Right => self.move_selected_right(),
+            self.notice = None;
+
+            // Only moving carries a stroke on, anything else ends it.
+            let painting = self.pen_down || key.modifiers.contains(KeyModifiers::SHIFT);
+            match key.code {
+                KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right if painting => {}
+                KeyCode::Char('p') => {}
+                _ => self.end_stroke(),
+            }
+
+            let message = match key.code {
+                KeyCode::Char(' ') | KeyCode::Char('x') => self.mark_selected_square(Some(true)),
+                KeyCode::Char('z') => self.mark_selected_square(Some(false)),
+                KeyCode::Backspace => self.mark_selected_square(None),
+                KeyCode::Char('p') => self.toggle_pen(),
+                KeyCode::Up => self
+                    .move_selected_up()
+                    .and_then(|_| self.paint_selected_square()),
+                KeyCode::Down => self
+                    .move_selected_down()
+                    .and_then(|_| self.paint_selected_square()),
+                KeyCode::Left => self
+                    .move_selected_left()
+                    .and_then(|_| self.paint_selected_square()),
+                KeyCode::Right => self
+                    .move_selected_right()
+                    .and_then(|_| self.paint_selected_square()),
                 KeyCode::Char('c') => self.check_assigned(),
+                KeyCode::Char('e') => self.export_puzzle(),
+                KeyCode::Char('a') => self.export_board(),
+                KeyCode::Esc => self.clear_mistakes(),
+                KeyCode::Char('f') => self.fix_mistakes(),
+                KeyCode::Char('u') => self.undo(),
+                KeyCode::Char('U') => self.undo_to_consistent(),
+                KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => self.redo(),
                 _ => Ok(ScreenMessage::Noop),
+            }?;
+
+            return match message {
+                ScreenMessage::Noop => self.detect_win(),
+                message => Ok(message),
+            };
+        }
+
+        if let Event::Mouse(mouse) = event {
+            return match self.handle_mouse(mouse)? {
+                ScreenMessage::Noop => self.detect_win(),
+                message => Ok(message),
             };
         }
         Ok(ScreenMessage::Noop)
@@ -332,17 +1452,297 @@ impl EventHandler for GameState {
 
 impl FrameRenderer for GameState {
     fn render_frame(&self, frame: &mut ratatui::prelude::Frame) -> color_eyre::Result<()> {
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Percentage(20), Constraint::Length(2)])
-            .split(frame.size());
+        let layout = game_layout(frame.size());
 
-        frame.render_widget(self.board_state.clone().render(), layout[0]);
+        let board = self.board_widget();
+        let viewport = board.viewport(layout[0]);
+        frame.render_widget(board, layout[0]);
         frame.render_widget(
-            Paragraph::new(format!("{:?}", self.board_state.invalid_tile).to_string()),
+            Paragraph::new(self.board_state.position_text(viewport)),
             layout[1],
         );
+        let check_text = match &self.notice {
+            Some(notice) => vec![Line::from(notice.clone())],
+            None => self.board_state.check_text(),
+        };
+        frame.render_widget(Paragraph::new(check_text), layout[2]);
+        frame.render_widget(Paragraph::new(self.status_text()), layout[3]);
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEvent;
+
+    /// A game of a small heart, whose clues only the heart itself matches.
+    fn heart() -> GameState {
+        let puzzle = formats::ascii::parse(".#.#.\n#####\n.###.\n..#..\n").unwrap();
+        GameState::from_puzzle(puzzle).unwrap()
+    }
+
+    fn press(game: &mut GameState, code: KeyCode) -> ScreenMessage {
+        press_with(game, code, KeyModifiers::NONE)
+    }
+
+    fn press_with(game: &mut GameState, code: KeyCode, modifiers: KeyModifiers) -> ScreenMessage {
+        game.handle_events(Event::Key(KeyEvent::new(code, modifiers)))
+            .unwrap()
+    }
+
+    fn row(game: &GameState, m: usize) -> Vec<Option<bool>> {
+        game.board_state.assigned_values[m].clone()
+    }
+
+    /// Selects a square and presses a key on it.
+    fn press_at(game: &mut GameState, square: (u8, u8), code: KeyCode) {
+        game.board_state.selected_square = square;
+        press(game, code);
+    }
+
+    fn cell(game: &GameState, m: usize, n: usize) -> Option<bool> {
+        game.board_state.assigned_values[m][n]
+    }
+
+    /// Marks the board as drawn, a row per line, with the symbols the board is drawn with.
+    fn draw(game: &mut GameState, drawing: &str) {
+        game.board_state.assigned_values = drawing
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|c| match c {
+                        '#' => Some(true),
+                        'X' => Some(false),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    #[test]
+    fn crossed_and_unmarked_cells_both_count_as_empty() {
+        let mut game = heart();
+        draw(&mut game, "X#.#X\n#####\n.###X\nXX#..\n");
+        assert!(matches!(
+            game.board_state.check_assigned(),
+            BoardValidity::Valid
+        ));
+    }
+
+    #[test]
+    fn any_grid_that_matches_the_clues_wins() {
+        // Both diagonals match these clues, and the puzzle came with the first one.
+        let puzzle = formats::ascii::parse("#.\n.#\n").unwrap();
+        let mut game = GameState::from_puzzle(puzzle).unwrap();
+        draw(&mut game, ".#\n#.\n");
+        assert!(matches!(
+            game.board_state.check_assigned(),
+            BoardValidity::Valid
+        ));
+        assert!(matches!(
+            press(&mut game, KeyCode::Char('c')),
+            ScreenMessage::ChangeScreen(Screen::End(_))
+        ));
+    }
+
+    #[test]
+    fn cells_are_not_held_to_one_of_several_solutions() {
+        let puzzle = formats::ascii::parse("#.\n.#\n").unwrap();
+        let mut game = GameState::from_puzzle(puzzle).unwrap();
+        assert!(!game.board_state.unique);
+
+        // The other diagonal, which the puzzle didn't come with, is on its way to winning.
+        press_at(&mut game, (0, 1), KeyCode::Char('x'));
+        press(&mut game, KeyCode::Char('c'));
+        assert!(game.board_state.mistakes.is_empty());
+        let text: Vec<String> = game
+            .board_state
+            .check_text()
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(text[1], "No line contradicts its clue so far.");
+
+        press(&mut game, KeyCode::Char('f'));
+        assert_eq!(cell(&game, 0, 1), Some(true));
+        assert!(game.notice.is_some());
+
+        // A line that can't match its clue any more is still reported.
+        press_at(&mut game, (0, 0), KeyCode::Char('x'));
+        assert_eq!(game.board_state.contradicting_lines().len(), 1);
+    }
+
+    #[test]
+    fn the_first_line_that_doesnt_match_is_reported() {
+        let mut game = heart();
+        draw(&mut game, ".#.#.\n#####\n.#.#.\n.....\n");
+        assert!(matches!(
+            game.board_state.check_assigned(),
+            BoardValidity::Invalid(BoardLine::Row(2))
+        ));
+
+        // With every row matching, the columns are checked.
+        draw(&mut game, "#.#..\n#####\n.###.\n..#..\n");
+        assert!(matches!(
+            game.board_state.check_assigned(),
+            BoardValidity::Invalid(BoardLine::Column(0))
+        ));
+    }
+
+    #[test]
+    fn fixing_mistakes_is_undone_in_one_step() {
+        let mut game = heart();
+        press_at(&mut game, (0, 0), KeyCode::Char('x'));
+        press_at(&mut game, (1, 2), KeyCode::Char('z'));
+        press_at(&mut game, (1, 0), KeyCode::Char('x'));
+
+        press(&mut game, KeyCode::Char('c'));
+        assert_eq!(game.board_state.mistakes, [(0, 0), (1, 2)]);
+        press(&mut game, KeyCode::Char('f'));
+        assert_eq!(
+            (cell(&game, 0, 0), cell(&game, 1, 2)),
+            (Some(false), Some(true))
+        );
+
+        press(&mut game, KeyCode::Char('u'));
+        assert_eq!(
+            (cell(&game, 0, 0), cell(&game, 1, 2)),
+            (Some(true), Some(false))
+        );
+        assert_eq!(cell(&game, 1, 0), Some(true));
+    }
+
+    #[test]
+    fn undoing_to_consistent_stops_at_the_first_consistent_board() {
+        let mut game = heart();
+        press_at(&mut game, (1, 0), KeyCode::Char('x'));
+        // Column 0 only has a single filled cell, so this can't be right.
+        press_at(&mut game, (0, 0), KeyCode::Char('x'));
+        press_at(&mut game, (3, 2), KeyCode::Char('x'));
+        assert!(!game.board_state.is_consistent());
+
+        press(&mut game, KeyCode::Char('U'));
+        assert!(game.board_state.is_consistent());
+        assert_eq!(
+            (cell(&game, 1, 0), cell(&game, 0, 0), cell(&game, 3, 2)),
+            (Some(true), None, None)
+        );
+
+        // What was undone can still be redone.
+        press_with(&mut game, KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(cell(&game, 0, 0), Some(true));
+    }
+
+    #[test]
+    fn painting_only_changes_cells_like_the_first_one() {
+        let mut game = heart();
+        press_at(&mut game, (0, 2), KeyCode::Char('z'));
+        press_at(&mut game, (0, 0), KeyCode::Char('x'));
+        for _ in 0..3 {
+            press_with(&mut game, KeyCode::Right, KeyModifiers::SHIFT);
+        }
+        let (t, f) = (Some(true), Some(false));
+        assert_eq!(row(&game, 0), [t, t, f, t, None]);
+
+        // Clearing paints over filled cells only.
+        press(&mut game, KeyCode::Backspace);
+        for _ in 0..3 {
+            press_with(&mut game, KeyCode::Left, KeyModifiers::SHIFT);
+        }
+        assert_eq!(row(&game, 0), [None, None, f, None, None]);
+    }
+
+    #[test]
+    fn a_painted_stroke_is_undone_as_one_edit() {
+        let mut game = heart();
+        press_at(&mut game, (1, 4), KeyCode::Char('x'));
+        press_at(&mut game, (1, 0), KeyCode::Char('x'));
+        for _ in 0..3 {
+            press_with(&mut game, KeyCode::Right, KeyModifiers::SHIFT);
+        }
+        let t = Some(true);
+        assert_eq!(row(&game, 1), [t, t, t, t, t]);
+
+        press(&mut game, KeyCode::Char('u'));
+        assert_eq!(row(&game, 1), [None, None, None, None, t]);
+    }
+
+    #[test]
+    fn with_the_pen_down_moving_paints_until_the_pen_is_lifted() {
+        let mut game = heart();
+        press(&mut game, KeyCode::Char('p'));
+        press_at(&mut game, (2, 0), KeyCode::Char('z'));
+        press(&mut game, KeyCode::Right);
+        press(&mut game, KeyCode::Right);
+        let f = Some(false);
+        assert_eq!(row(&game, 2), [f, f, f, None, None]);
+
+        press(&mut game, KeyCode::Char('p'));
+        press(&mut game, KeyCode::Right);
+        assert_eq!(row(&game, 2), [f, f, f, None, None]);
+    }
+
+    #[test]
+    fn any_other_key_ends_the_stroke() {
+        let mut game = heart();
+        press_at(&mut game, (3, 0), KeyCode::Char('x'));
+        press_with(&mut game, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut game, KeyCode::Char('c'));
+        press_with(&mut game, KeyCode::Right, KeyModifiers::SHIFT);
+        let t = Some(true);
+        assert_eq!(row(&game, 3), [t, t, None, None, None]);
+
+        // Moving without shift ends it too.
+        press(&mut game, KeyCode::Char('x'));
+        press(&mut game, KeyCode::Right);
+        press_with(&mut game, KeyCode::Right, KeyModifiers::SHIFT);
+        assert_eq!(row(&game, 3), [t, t, t, None, None]);
+    }
+
+    #[test]
+    fn a_seed_always_generates_the_same_board() {
+        let settings = GameSettings {
+            width: 12,
+            height: 8,
+            seed: 42,
+            ..GameSettings::default()
+        };
+        let board = BoardState::from(settings);
+        assert_eq!(board.true_values, BoardState::from(settings).true_values);
+
+        let other_seed = GameSettings {
+            seed: 43,
+            ..settings
+        };
+        assert_ne!(board.true_values, BoardState::from(other_seed).true_values);
+    }
+
+    #[test]
+    fn generated_boards_keep_to_the_requested_density() {
+        for density in [0.3, 0.5, 0.7] {
+            let fills: Vec<f64> = (0..3)
+                .map(|seed| {
+                    let settings = GameSettings {
+                        width: 20,
+                        height: 20,
+                        density,
+                        seed,
+                        ..GameSettings::default()
+                    };
+                    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                    filled_share(&generate_solvable(&settings, &mut rng, &mut 0).0)
+                })
+                .collect();
+            let average = fills.iter().sum::<f64>() / fills.len() as f64;
+            assert!(
+                (average - density).abs() < 0.06,
+                "asked for {}, got {}",
+                density,
+                average
+            );
+        }
+    }
+}
@@ -0,0 +1,501 @@
+// Saving games in progress so they can be continued later.
+//
+// A save is a text file with one value per line, each starting with its name. Grids are written a
+// row per line with the symbols the board is drawn with, and every undoable edit gets a line of
+// `row,column,before,after` changes.
+
+use std::iter::Peekable;
+use std::str::{FromStr, Lines};
+use std::time::{Duration, Instant};
+
+use crate::history::{CellChange, History};
+use crate::storage;
+
+use super::{
+    cell_char, has_unique_solution, next_revision, rate_board, BoardState, Difficulty, GameMode,
+    GameSettings, GameState,
+};
+
+const SAVE_FILE: &str = "save.txt";
+
+// Written by the panic and error hooks when the app crashes during a game.
+const RECOVERY_FILE: &str = "recovery.txt";
+
+// First line of every save, so older formats can be told apart if this one ever changes.
+const SAVE_HEADER: &str = "no2 save 1";
+
+impl GameState {
+    pub fn has_save() -> bool {
+        storage::exists(SAVE_FILE)
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        storage::write(SAVE_FILE, &self.to_save_text())
+    }
+
+    /// Loads the saved game. The clock carries on from where the save left it.
+    pub fn load_save() -> Result<GameState, String> {
+        GameState::load_from(SAVE_FILE)
+    }
+
+    /// Forgets the saved game, once it has been finished.
+    pub fn delete_save() -> std::io::Result<()> {
+        storage::remove(SAVE_FILE)
+    }
+
+    pub fn has_recovery() -> bool {
+        storage::exists(RECOVERY_FILE)
+    }
+
+    pub fn save_recovery(&self) -> std::io::Result<()> {
+        storage::write(RECOVERY_FILE, &self.to_save_text())
+    }
+
+    /// Loads the game that was being played when the app crashed. The recovery file is removed
+    /// once it has been read, so a crash is only offered for restoring once.
+    pub fn load_recovery() -> Result<GameState, String> {
+        let game_state = GameState::load_from(RECOVERY_FILE)?;
+        // Failing to remove the file only means it's offered again next time.
+        let _ = storage::remove(RECOVERY_FILE);
+        Ok(game_state)
+    }
+
+    fn load_from(name: &str) -> Result<GameState, String> {
+        match storage::read(name) {
+            Ok(Some(text)) => GameState::from_save_text(&text),
+            Ok(None) => Err(String::from("There is no saved game.")),
+            Err(error) => Err(format!("Couldn't read the saved game: {}", error)),
+        }
+    }
+
+    fn to_save_text(&self) -> String {
+        let settings = &self.settings;
+        let board = &self.board_state;
+
+        let mut lines = vec![
+            String::from(SAVE_HEADER),
+            format!("width {}", settings.width),
+            format!("height {}", settings.height),
+            format!("difficulty {}", settings.difficulty),
+            format!("density {}", settings.density),
+            format!("seed {}", settings.seed),
+            format!("strict_check {}", settings.strict_check),
+            format!("mode {}", settings.mode),
+            format!("cell_width {}", settings.cell_width),
+            format!("crosshair {}", settings.crosshair),
+        ];
+        if let Some(name) = &self.puzzle_name {
+            lines.push(format!("name {}", name));
+        }
+        if let Some(lives) = self.lives {
+            lines.push(format!("lives {}", lives));
+        }
+        lines.push(format!(
+            "selected {} {}",
+            board.selected_square.0, board.selected_square.1
+        ));
+        lines.push(format!("elapsed {}", self.elapsed().as_millis()));
+
+        for counts in board.row_counts.iter() {
+            lines.push(format!("row_clue {}", join_counts(counts)));
+        }
+        for counts in board.column_counts.iter() {
+            lines.push(format!("column_clue {}", join_counts(counts)));
+        }
+        for row in board.true_values.iter() {
+            let row: String = row.iter().map(|v| cell_char(v.then_some(true))).collect();
+            lines.push(format!("solution {}", row));
+        }
+        for row in board.assigned_values.iter() {
+            let row: String = row.iter().map(|v| cell_char(*v)).collect();
+            lines.push(format!("board {}", row));
+        }
+
+        let (undo, redo) = self.history.edits();
+        for (name, edits) in [("undo", undo), ("redo", redo)] {
+            for changes in edits {
+                let changes: Vec<String> = changes
+                    .iter()
+                    .map(|c| {
+                        format!(
+                            "{},{},{},{}",
+                            c.row,
+                            c.column,
+                            cell_char(c.before),
+                            cell_char(c.after)
+                        )
+                    })
+                    .collect();
+                lines.push(format!("{} {}", name, changes.join(" ")));
+            }
+        }
+
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    fn from_save_text(text: &str) -> Result<GameState, String> {
+        let mut reader = SaveReader {
+            lines: text.lines().peekable(),
+            line_number: 0,
+        };
+
+        if reader.lines.next() != Some(SAVE_HEADER) {
+            return Err(String::from(
+                "The saved game isn't in a format this version knows.",
+            ));
+        }
+        reader.line_number = 1;
+
+        let width: u8 = reader.parse("width")?;
+        let height: u8 = reader.parse("height")?;
+        if width == 0 || height == 0 {
+            return Err(String::from("The saved board has no cells."));
+        }
+        let difficulty = reader.parse_with("difficulty", |v| {
+            [
+                Difficulty::Easy,
+                Difficulty::Medium,
+                Difficulty::Hard,
+                Difficulty::Expert,
+            ]
+            .into_iter()
+            .find(|d| d.to_string() == v)
+        })?;
+        let density = reader.parse("density")?;
+        let seed = reader.parse("seed")?;
+        let strict_check = reader.parse("strict_check")?;
+        let mode = reader.parse_with("mode", |v| {
+            [GameMode::Classic, GameMode::Lives]
+                .into_iter()
+                .find(|m| m.to_string() == v)
+        })?;
+        let cell_width = reader.parse("cell_width")?;
+        let crosshair = reader.parse("crosshair")?;
+        let settings = GameSettings {
+            width,
+            height,
+            difficulty,
+            density,
+            seed,
+            strict_check,
+            mode,
+            cell_width,
+            crosshair,
+        };
+
+        let puzzle_name = match reader.has_next("name") {
+            true => Some(reader.value("name")?.to_string()),
+            false => None,
+        };
+        let lives = match reader.has_next("lives") {
+            true => Some(reader.parse("lives")?),
+            false => None,
+        };
+        let selected_square = reader.parse_with("selected", |v| {
+            let (m, n) = v.split_once(' ')?;
+            Some((m.parse().ok()?, n.parse().ok()?))
+        })?;
+        if selected_square.0 >= height || selected_square.1 >= width {
+            return Err(reader.error("the selected square is off the board"));
+        }
+        let played = Duration::from_millis(reader.parse("elapsed")?);
+
+        let row_counts = reader.parse_lines("row_clue", height, parse_counts)?;
+        let column_counts = reader.parse_lines("column_clue", width, parse_counts)?;
+        let true_values = reader.parse_lines("solution", height, |v| {
+            parse_row(v, width)?
+                .into_iter()
+                .map(|cell| match cell {
+                    Some(true) => Some(true),
+                    None => Some(false),
+                    Some(false) => None,
+                })
+                .collect()
+        })?;
+        let assigned_values = reader.parse_lines("board", height, |v| parse_row(v, width))?;
+
+        let parse_edit = |v: &str| {
+            v.split(' ')
+                .map(|change| parse_change(change, width, height))
+                .collect::<Option<Vec<CellChange>>>()
+        };
+        let undo = reader.parse_repeated("undo", parse_edit)?;
+        let redo = reader.parse_repeated("redo", parse_edit)?;
+
+        if let Some(line) = reader.lines.next() {
+            reader.line_number += 1;
+            return Err(reader.error(&format!("didn't expect \"{}\"", line)));
+        }
+
+        let rating = rate_board(&row_counts, &column_counts);
+        let unique = has_unique_solution(&row_counts, &column_counts, rating);
+        Ok(GameState {
+            settings,
+            board_state: BoardState {
+                width,
+                height,
+                true_values,
+                row_counts,
+                column_counts,
+                assigned_values,
+                selected_square,
+                invalid_line: None,
+                mistakes: Vec::new(),
+                rating,
+                unique,
+            },
+            history: History::from_edits(undo, redo),
+            lives,
+            stroke: None,
+            pen_down: false,
+            played,
+            resumed: Instant::now(),
+            puzzle_name,
+            notice: None,
+            revision: next_revision(),
+        })
+    }
+}
+
+/// Reads the named lines of a save in order, keeping track of the line number for errors.
+struct SaveReader<'a> {
+    lines: Peekable<Lines<'a>>,
+    line_number: usize,
+}
+
+impl<'a> SaveReader<'a> {
+    fn error(&self, message: &str) -> String {
+        format!(
+            "The saved game is damaged: line {}: {}.",
+            self.line_number, message
+        )
+    }
+
+    fn has_next(&mut self, name: &str) -> bool {
+        self.lines
+            .peek()
+            .is_some_and(|line| split_line(line).0 == name)
+    }
+
+    fn value(&mut self, name: &str) -> Result<&'a str, String> {
+        let line = self.lines.next();
+        self.line_number += 1;
+        match line.map(split_line) {
+            Some((found, value)) if found == name => Ok(value),
+            _ => Err(self.error(&format!("expected {}", name))),
+        }
+    }
+
+    fn parse_with<T>(
+        &mut self,
+        name: &str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<T, String> {
+        let value = self.value(name)?;
+        parse(value).ok_or_else(|| self.error(&format!("couldn't read {} \"{}\"", name, value)))
+    }
+
+    fn parse<T: FromStr>(&mut self, name: &str) -> Result<T, String> {
+        self.parse_with(name, |v| v.parse().ok())
+    }
+
+    /// Reads `count` lines with the same name, such as the rows of a grid.
+    fn parse_lines<T>(
+        &mut self,
+        name: &str,
+        count: u8,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<Vec<T>, String> {
+        (0..count).map(|_| self.parse_with(name, &parse)).collect()
+    }
+
+    /// Reads however many lines in a row have the same name.
+    fn parse_repeated<T>(
+        &mut self,
+        name: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<Vec<T>, String> {
+        let mut values = Vec::new();
+        while self.has_next(name) {
+            values.push(self.parse_with(name, &parse)?);
+        }
+        Ok(values)
+    }
+}
+
+/// Splits a line into its name and value. The value is empty if there isn't one.
+fn split_line(line: &str) -> (&str, &str) {
+    line.split_once(' ').unwrap_or((line, ""))
+}
+
+fn join_counts(counts: &[u8]) -> String {
+    let counts: Vec<String> = counts.iter().map(|c| c.to_string()).collect();
+    counts.join(" ")
+}
+
+fn parse_counts(value: &str) -> Option<Vec<u8>> {
+    value
+        .split_whitespace()
+        .map(|c| c.parse().ok().filter(|c| *c > 0))
+        .collect()
+}
+
+fn parse_cell(symbol: char) -> Option<Option<bool>> {
+    [Some(true), Some(false), None]
+        .into_iter()
+        .find(|value| cell_char(*value) == symbol)
+}
+
+fn parse_row(value: &str, width: u8) -> Option<Vec<Option<bool>>> {
+    let row: Vec<Option<bool>> = value.chars().map(parse_cell).collect::<Option<_>>()?;
+    (row.len() == width as usize).then_some(row)
+}
+
+fn parse_change(change: &str, width: u8, height: u8) -> Option<CellChange> {
+    let mut parts = change.split(',');
+    let row: u8 = parts.next()?.parse().ok()?;
+    let column: u8 = parts.next()?.parse().ok()?;
+    let before = parse_cell(single_char(parts.next()?)?)?;
+    let after = parse_cell(single_char(parts.next()?)?)?;
+    if parts.next().is_some() || row >= height || column >= width {
+        return None;
+    }
+
+    Some(CellChange {
+        row,
+        column,
+        before,
+        after,
+    })
+}
+
+fn single_char(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::Puzzle;
+
+    fn game_in_progress() -> GameState {
+        let mut game_state = GameState::from(GameSettings {
+            width: 6,
+            height: 4,
+            seed: 7,
+            mode: GameMode::Lives,
+            cell_width: 2,
+            crosshair: true,
+            ..GameSettings::default()
+        });
+
+        // A wrong fill costs a life, then one edit is undone so there's something to redo too.
+        let board = &game_state.board_state;
+        let (m, n) = (0..board.height)
+            .flat_map(|m| (0..board.width).map(move |n| (m, n)))
+            .find(|(m, n)| !board.true_values[*m as usize][*n as usize])
+            .unwrap();
+        game_state.board_state.selected_square = (m, n);
+        game_state.assign_selected_square(Some(true)).unwrap();
+        game_state.board_state.selected_square = (3, 5);
+        game_state.mark_selected_square(Some(false)).unwrap();
+        game_state.board_state.selected_square = (2, 1);
+        game_state.mark_selected_square(Some(true)).unwrap();
+        game_state.undo().unwrap();
+        game_state.played = Duration::from_secs(95);
+        game_state
+    }
+
+    /// The save without its elapsed time, which keeps ticking between two saves.
+    fn without_clock(text: &str) -> Vec<&str> {
+        text.lines()
+            .filter(|line| !line.starts_with("elapsed "))
+            .collect()
+    }
+
+    #[test]
+    fn a_saved_game_loads_as_it_was() {
+        let game_state = game_in_progress();
+        let text = game_state.to_save_text();
+        let loaded = GameState::from_save_text(&text).unwrap();
+
+        assert_eq!(without_clock(&loaded.to_save_text()), without_clock(&text));
+        assert_eq!(loaded.lives, Some(2));
+        assert_eq!(
+            loaded.settings.puzzle_code(),
+            game_state.settings.puzzle_code()
+        );
+        assert_eq!(loaded.board_state.selected_square, (2, 1));
+        assert!(loaded.played >= Duration::from_secs(95));
+
+        let (undo, redo) = loaded.history.edits();
+        assert_eq!((undo.len(), redo.len()), (2, 1));
+    }
+
+    #[test]
+    fn undo_and_redo_carry_on_after_loading() {
+        let mut loaded = GameState::from_save_text(&game_in_progress().to_save_text()).unwrap();
+
+        loaded.redo().unwrap();
+        assert_eq!(loaded.board_state.assigned_values[2][1], Some(true));
+        loaded.undo().unwrap();
+        loaded.undo().unwrap();
+        assert_eq!(loaded.board_state.assigned_values[3][5], None);
+    }
+
+    #[test]
+    fn an_imported_name_stays_on_one_line() {
+        let puzzle = Puzzle {
+            name: Some(String::from("Heart\nof the  matter")),
+            row_counts: vec![vec![1], vec![]],
+            column_counts: vec![vec![1]],
+            solution: None,
+        };
+        let game_state = GameState::from_puzzle(puzzle).unwrap();
+        assert_eq!(
+            game_state.puzzle_name.as_deref(),
+            Some("Heart of the matter")
+        );
+
+        let loaded = GameState::from_save_text(&game_state.to_save_text()).unwrap();
+        assert_eq!(loaded.puzzle_name.as_deref(), Some("Heart of the matter"));
+    }
+
+    #[test]
+    fn damaged_saves_are_reported_with_their_line() {
+        let text = game_in_progress().to_save_text();
+
+        let other_version = text.replacen(SAVE_HEADER, "no2 save 0", 1);
+        assert_eq!(
+            GameState::from_save_text(&other_version).err().unwrap(),
+            "The saved game isn't in a format this version knows."
+        );
+
+        let short_row = text.replacen("\nboard ", "\nboard .", 1);
+        let error = GameState::from_save_text(&short_row).err().unwrap();
+        let line = text.lines().position(|l| l.starts_with("board ")).unwrap() + 1;
+        assert!(
+            error.starts_with(&format!("The saved game is damaged: line {}:", line)),
+            "{}",
+            error
+        );
+
+        let cut_off: String = text.lines().take(12).map(|l| format!("{}\n", l)).collect();
+        assert!(GameState::from_save_text(&cut_off).is_err());
+
+        let off_the_board = text.replacen("selected 2 1", "selected 4 1", 1);
+        assert!(GameState::from_save_text(&off_the_board)
+            .err()
+            .unwrap()
+            .contains("the selected square is off the board"));
+
+        let extra = format!("{}surprise\n", text);
+        assert!(GameState::from_save_text(&extra)
+            .err()
+            .unwrap()
+            .contains("didn't expect \"surprise\""));
+    }
+}
@@ -1,16 +1,20 @@
 use crate::app::Screen;
-use crate::components::Button;
+use crate::components::{self, Button};
+use crate::terminal;
 use crate::traits::{EventHandler, FrameRenderer, ScreenMessage};
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, MouseEvent};
 use ratatui::{prelude::*, widgets::Paragraph};
 use std::collections::HashMap;
+use std::rc::Rc;
 
+use super::game::GameState;
 use super::settings::SettingsState;
 
 enum ButtonAction {
+    RestoreGame,
+    ContinueGame,
     StartGame,
     Quit,
-    Noop,
 }
 
 pub struct HomeState {
@@ -18,6 +22,8 @@ pub struct HomeState {
     menu_buttons: Vec<crate::components::Button>,
     // Keys are the button IDs.
     action_map: HashMap<String, ButtonAction>,
+    // Why the saved game couldn't be continued.
+    error: Option<String>,
 }
 
 impl HomeState {
@@ -35,6 +41,27 @@ impl HomeState {
         Ok(ScreenMessage::Noop)
     }
 
+    fn click_button(&mut self, mouse: MouseEvent) -> color_eyre::Result<ScreenMessage> {
+        let layout = self.layout(terminal::screen_area()?);
+        match components::clicked_button(&layout[..self.menu_buttons.len()], mouse) {
+            Some(i) => {
+                self.selected_button = i as u8;
+                self.select_button()
+            }
+            None => Ok(ScreenMessage::Noop),
+        }
+    }
+
+    /// A row for each button, and one below them for the error.
+    fn layout(&self, area: Rect) -> Rc<[Rect]> {
+        let mut constraints = vec![Constraint::Length(3); self.menu_buttons.len()];
+        constraints.push(Constraint::Length(1));
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area)
+    }
+
     fn select_button(&mut self) -> color_eyre::Result<ScreenMessage> {
         // TODO: remove all these unwraps
         let action = self
@@ -49,11 +76,24 @@ impl HomeState {
             .unwrap();
 
         match action {
+            ButtonAction::RestoreGame => match GameState::load_recovery() {
+                Ok(game_state) => Ok(ScreenMessage::ChangeScreen(Screen::Game(game_state))),
+                Err(error) => {
+                    self.error = Some(error);
+                    Ok(ScreenMessage::Noop)
+                }
+            },
+            ButtonAction::ContinueGame => match GameState::load_save() {
+                Ok(game_state) => Ok(ScreenMessage::ChangeScreen(Screen::Game(game_state))),
+                Err(error) => {
+                    self.error = Some(error);
+                    Ok(ScreenMessage::Noop)
+                }
+            },
             ButtonAction::StartGame => Ok(ScreenMessage::ChangeScreen(Screen::Settings(
                 SettingsState::default(),
             ))),
             ButtonAction::Quit => Ok(ScreenMessage::Exit),
-            ButtonAction::Noop => Ok(ScreenMessage::Noop),
         }
     }
 }
@@ -65,13 +105,38 @@ impl Default for HomeState {
         let play_button = Button::new(play_button_id.clone(), String::from("Play!"));
         let quit_button = Button::new(quit_button_id.clone(), String::from("Quit!"));
 
+        let mut menu_buttons = vec![play_button, quit_button];
+        let mut action_map = HashMap::from([
+            (play_button_id, ButtonAction::StartGame),
+            (quit_button_id, ButtonAction::Quit),
+        ]);
+
+        if GameState::has_save() {
+            let continue_button_id = String::from("continue_button");
+            menu_buttons.insert(
+                0,
+                Button::new(continue_button_id.clone(), String::from("Continue")),
+            );
+            action_map.insert(continue_button_id, ButtonAction::ContinueGame);
+        }
+
+        if GameState::has_recovery() {
+            let restore_button_id = String::from("restore_button");
+            menu_buttons.insert(
+                0,
+                Button::new(
+                    restore_button_id.clone(),
+                    String::from("Restore the game from before the crash"),
+                ),
+            );
+            action_map.insert(restore_button_id, ButtonAction::RestoreGame);
+        }
+
         HomeState {
             selected_button: 0,
-            menu_buttons: vec![play_button, quit_button],
-            action_map: HashMap::from([
-                (play_button_id, ButtonAction::StartGame),
-                (quit_button_id, ButtonAction::Quit),
-            ]),
+            menu_buttons,
+            action_map,
+            error: None,
         }
     }
 }
@@ -91,16 +156,17 @@ impl EventHandler for HomeState {
             };
         }
 
+        if let Event::Mouse(mouse) = event {
+            return self.click_button(mouse);
+        }
+
         Ok(ScreenMessage::Noop)
     }
 }
 
 impl FrameRenderer for HomeState {
     fn render_frame(&self, frame: &mut ratatui::prelude::Frame) -> color_eyre::Result<()> {
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(3); self.menu_buttons.len()])
-            .split(frame.size());
+        let layout = self.layout(frame.size());
 
         for (i, menu_button) in self.menu_buttons.iter().enumerate() {
             let mut paragraph_widget = Paragraph::new(menu_button.text.clone());
@@ -117,6 +183,13 @@ impl FrameRenderer for HomeState {
             frame.render_widget(paragraph_widget, layout[i]);
         }
 
+        if let Some(error) = &self.error {
+            frame.render_widget(
+                Paragraph::new(error.clone()).red(),
+                layout[self.menu_buttons.len()],
+            );
+        }
+
         Ok(())
     }
 }
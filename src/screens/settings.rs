@@ -1,6 +1,7 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, MouseEvent};
 use ratatui::{
     layout::{Constraint, Direction, Layout},
     prelude::*,
@@ -9,17 +10,42 @@ use ratatui::{
 
 use crate::{
     app::Screen,
-    components::Button,
+    components::{self, Button},
+    terminal,
     traits::{EventHandler, FrameRenderer, ScreenMessage},
 };
 
-use super::game::{GameSettings, GameState};
+use super::game::{min_density, reachable_difficulties, Difficulty, GameMode, GameSettings};
 use super::home::HomeState;
 
+// Largest width or height a board can have.
+const MAX_BOARD_SIZE: u8 = 50;
+
+// Range and step of the fill density control.
+const MIN_DENSITY: f64 = 0.3;
+const MAX_DENSITY: f64 = 0.8;
+const DENSITY_STEP: f64 = 0.1;
+
+// Widest a board cell can be drawn, in terminal columns.
+const MAX_CELL_WIDTH: u8 = 3;
+
+#[derive(Clone, Copy)]
+enum Field {
+    Width,
+    Height,
+    Density,
+    Seed,
+    Difficulty,
+    StrictCheck,
+    Mode,
+    CellWidth,
+    Crosshair,
+}
+
 enum ButtonAction {
-    StartGame(GameSettings),
+    Edit(Field),
+    StartGame,
     Back,
-    Noop,
 }
 
 pub struct SettingsState {
@@ -27,14 +53,19 @@ pub struct SettingsState {
     menu_buttons: Vec<crate::components::Button>,
     // Keys are the button IDs.
     action_map: HashMap<String, ButtonAction>,
-    // TODO: move to system where buttons will mutate this variable and then when we hit start we
-    // use this variable to initialise the game state.
-    // settings: GameSettings,
+    // The fields edit these settings, and the start button starts a game with them.
+    settings: GameSettings,
+    // Why the last attempt to start a game or to type a digit was refused.
+    error: Option<String>,
+    // Set once a digit has been typed into the selected field. The first digit replaces the value
+    // the field had, and the ones after it are added on the end.
+    typing: bool,
 }
 
 impl SettingsState {
     fn move_prev_button(&mut self) -> color_eyre::Result<ScreenMessage> {
         self.selected_button = self.selected_button.saturating_sub(1);
+        self.typing = false;
         Ok(ScreenMessage::Noop)
     }
 
@@ -43,14 +74,14 @@ impl SettingsState {
             return Ok(ScreenMessage::Noop);
         }
         self.selected_button = self.selected_button.saturating_add(1);
+        self.typing = false;
 
         Ok(ScreenMessage::Noop)
     }
 
-    fn select_button(&mut self) -> color_eyre::Result<ScreenMessage> {
+    fn selected_action(&self) -> &ButtonAction {
         // TODO: remove all these unwraps
-        let action = self
-            .action_map
+        self.action_map
             .get(
                 &self
                     .menu_buttons
@@ -58,43 +89,345 @@ impl SettingsState {
                     .unwrap()
                     .id,
             )
-            .unwrap();
+            .unwrap()
+    }
 
-        match action {
-            ButtonAction::StartGame(settings) => Ok(ScreenMessage::ChangeScreen(Screen::Game(
-                GameState::from(settings.clone()),
-            ))),
+    fn selected_field(&self) -> Option<Field> {
+        match self.selected_action() {
+            ButtonAction::Edit(field) => Some(*field),
+            _ => None,
+        }
+    }
+
+    fn refresh_button_text(&mut self) {
+        for button in self.menu_buttons.iter_mut() {
+            if let Some(ButtonAction::Edit(field)) = self.action_map.get(&button.id) {
+                button.text = field_text(*field, &self.settings);
+            }
+        }
+    }
+
+    fn adjust_selected(&mut self, forward: bool) -> color_eyre::Result<ScreenMessage> {
+        let Some(field) = self.selected_field() else {
+            return Ok(ScreenMessage::Noop);
+        };
+
+        let settings = &mut self.settings;
+        match field {
+            Field::Width => settings.width = step_size(settings.width, forward),
+            Field::Height => settings.height = step_size(settings.height, forward),
+            Field::Density => {
+                let density = match forward {
+                    true => settings.density + DENSITY_STEP,
+                    false => settings.density - DENSITY_STEP,
+                };
+                settings.density = density.clamp(MIN_DENSITY, MAX_DENSITY);
+            }
+            Field::Seed => {
+                settings.seed = match forward {
+                    true => settings.seed.wrapping_add(1),
+                    false => settings.seed.wrapping_sub(1),
+                }
+            }
+            Field::Difficulty => {
+                settings.difficulty = match forward {
+                    true => settings.difficulty.next(),
+                    false => settings.difficulty.prev(),
+                }
+            }
+            Field::StrictCheck => settings.strict_check = !settings.strict_check,
+            Field::Mode => settings.mode = next_mode(settings.mode),
+            Field::CellWidth => {
+                settings.cell_width = match forward {
+                    true => settings.cell_width + 1,
+                    false => settings.cell_width - 1,
+                }
+                .clamp(1, MAX_CELL_WIDTH)
+            }
+            Field::Crosshair => settings.crosshair = !settings.crosshair,
+        }
+
+        self.typing = false;
+        self.refresh_button_text();
+        Ok(ScreenMessage::Noop)
+    }
+
+    fn type_digit(&mut self, digit: u8) -> color_eyre::Result<ScreenMessage> {
+        // Out of range values are allowed while typing, and only refused when starting the game.
+        // A digit that would make the value too big to hold at all is refused straight away.
+        let field = self.selected_field();
+        let typing = self.typing;
+        let settings = &mut self.settings;
+        let typed = |value: u8| match typing {
+            true => append_digit(value, digit),
+            false => Some(digit),
+        };
+        let fits = match field {
+            Some(Field::Width) => typed(settings.width).map(|w| settings.width = w),
+            Some(Field::Height) => typed(settings.height).map(|h| settings.height = h),
+            Some(Field::Density) => typed(density_percent(settings.density))
+                .map(|percent| settings.density = percent as f64 / 100.0),
+            Some(Field::Seed) => match typing {
+                true => settings
+                    .seed
+                    .checked_mul(10)
+                    .and_then(|s| s.checked_add(digit as u64)),
+                false => Some(digit as u64),
+            }
+            .map(|seed| settings.seed = seed),
+            Some(
+                Field::Difficulty
+                | Field::StrictCheck
+                | Field::Mode
+                | Field::CellWidth
+                | Field::Crosshair,
+            )
+            | None => return Ok(ScreenMessage::Noop),
+        };
+
+        self.typing = true;
+        self.error = match (fits, field) {
+            (Some(()), _) => None,
+            (None, Some(Field::Width)) => Some(size_error("Width")),
+            (None, Some(Field::Height)) => Some(size_error("Height")),
+            (None, Some(Field::Density)) => Some(density_error()),
+            (None, _) => Some(format!("The seed can't be larger than {}.", u64::MAX)),
+        };
+        self.refresh_button_text();
+        Ok(ScreenMessage::Noop)
+    }
+
+    fn erase_digit(&mut self) -> color_eyre::Result<ScreenMessage> {
+        let field = self.selected_field();
+        let settings = &mut self.settings;
+        match field {
+            Some(Field::Width) => settings.width /= 10,
+            Some(Field::Height) => settings.height /= 10,
+            Some(Field::Density) => {
+                settings.density = (density_percent(settings.density) / 10) as f64 / 100.0
+            }
+            Some(Field::Seed) => settings.seed /= 10,
+            Some(
+                Field::Difficulty
+                | Field::StrictCheck
+                | Field::Mode
+                | Field::CellWidth
+                | Field::Crosshair,
+            )
+            | None => return Ok(ScreenMessage::Noop),
+        }
+
+        // Digits typed after erasing one carry on from what's left.
+        self.typing = true;
+        self.refresh_button_text();
+        Ok(ScreenMessage::Noop)
+    }
+
+    /// Checks the settings before a game is started with them.
+    fn validate(&self) -> Result<(), String> {
+        let settings = &self.settings;
+        for (name, value) in [("Width", settings.width), ("Height", settings.height)] {
+            if !(1..=MAX_BOARD_SIZE).contains(&value) {
+                return Err(size_error(name));
+            }
+        }
+
+        let percent = density_percent(settings.density);
+        if !(density_percent(MIN_DENSITY)..=density_percent(MAX_DENSITY)).contains(&percent) {
+            return Err(density_error());
+        }
+        let min_percent = density_percent(min_density(settings.width, settings.height));
+        if percent < min_percent {
+            return Err(format!(
+                "A {}x{} board can't be sparser than {}%.",
+                settings.width, settings.height, min_percent
+            ));
+        }
+
+        let reachable = reachable_difficulties(settings.width, settings.height, settings.density);
+        if !reachable.contains(&settings.difficulty) {
+            let difficulties = match reachable.start() == reachable.end() {
+                true => reachable.start().to_string(),
+                false => format!("{} to {}", reachable.start(), reachable.end()),
+            };
+            return Err(format!(
+                "A {}x{} board {}% full only comes out {}.",
+                settings.width, settings.height, percent, difficulties
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn click_button(&mut self, mouse: MouseEvent) -> color_eyre::Result<ScreenMessage> {
+        let layout = self.layout(terminal::screen_area()?);
+        match components::clicked_button(&layout[..self.menu_buttons.len()], mouse) {
+            Some(i) => {
+                if self.selected_button != i as u8 {
+                    self.selected_button = i as u8;
+                    self.typing = false;
+                }
+                self.select_button()
+            }
+            None => Ok(ScreenMessage::Noop),
+        }
+    }
+
+    /// A row for each button, and one below them for the error.
+    fn layout(&self, area: Rect) -> Rc<[Rect]> {
+        let mut constraints = vec![Constraint::Length(3); self.menu_buttons.len()];
+        constraints.push(Constraint::Length(1));
+        Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area)
+    }
+
+    fn select_button(&mut self) -> color_eyre::Result<ScreenMessage> {
+        match self.selected_action() {
+            ButtonAction::StartGame => match self.validate() {
+                Ok(()) => Ok(ScreenMessage::StartGame(self.settings)),
+                Err(error) => {
+                    self.error = Some(error);
+                    Ok(ScreenMessage::Noop)
+                }
+            },
+            ButtonAction::Edit(Field::Difficulty) => {
+                // Enter cycles round to the easiest difficulty again.
+                self.settings.difficulty = match self.settings.difficulty {
+                    Difficulty::Expert => Difficulty::Easy,
+                    difficulty => difficulty.next(),
+                };
+                self.refresh_button_text();
+                Ok(ScreenMessage::Noop)
+            }
+            ButtonAction::Edit(Field::StrictCheck) => {
+                self.settings.strict_check = !self.settings.strict_check;
+                self.refresh_button_text();
+                Ok(ScreenMessage::Noop)
+            }
+            ButtonAction::Edit(Field::Mode) => {
+                self.settings.mode = next_mode(self.settings.mode);
+                self.refresh_button_text();
+                Ok(ScreenMessage::Noop)
+            }
+            ButtonAction::Edit(Field::CellWidth) => {
+                self.settings.cell_width = self.settings.cell_width % MAX_CELL_WIDTH + 1;
+                self.refresh_button_text();
+                Ok(ScreenMessage::Noop)
+            }
+            ButtonAction::Edit(Field::Crosshair) => {
+                self.settings.crosshair = !self.settings.crosshair;
+                self.refresh_button_text();
+                Ok(ScreenMessage::Noop)
+            }
+            ButtonAction::Edit(Field::Seed) => {
+                self.settings.seed = rand::random();
+                self.refresh_button_text();
+                Ok(ScreenMessage::Noop)
+            }
+            ButtonAction::Edit(_) => Ok(ScreenMessage::Noop),
             ButtonAction::Back => Ok(ScreenMessage::ChangeScreen(Screen::Home(
                 HomeState::default(),
             ))),
-            ButtonAction::Noop => Ok(ScreenMessage::Noop),
         }
     }
 }
 
+fn step_size(size: u8, forward: bool) -> u8 {
+    match forward {
+        true => size.saturating_add(1),
+        false => size.saturating_sub(1),
+    }
+    .clamp(1, MAX_BOARD_SIZE)
+}
+
+/// The value with the digit added on the end, or `None` if that doesn't fit in a `u8`.
+fn append_digit(value: u8, digit: u8) -> Option<u8> {
+    value.checked_mul(10).and_then(|v| v.checked_add(digit))
+}
+
+fn size_error(name: &str) -> String {
+    format!("{} must be between 1 and {}.", name, MAX_BOARD_SIZE)
+}
+
+fn density_error() -> String {
+    format!(
+        "Density must be between {}% and {}%.",
+        density_percent(MIN_DENSITY),
+        density_percent(MAX_DENSITY)
+    )
+}
+
+fn next_mode(mode: GameMode) -> GameMode {
+    match mode {
+        GameMode::Classic => GameMode::Lives,
+        GameMode::Lives => GameMode::Classic,
+    }
+}
+
+fn density_percent(density: f64) -> u8 {
+    (density * 100.0).round() as u8
+}
+
+fn field_text(field: Field, settings: &GameSettings) -> String {
+    match field {
+        Field::Width => format!("Width: < {} >", settings.width),
+        Field::Height => format!("Height: < {} >", settings.height),
+        Field::Density => format!("Density: < {}% >", density_percent(settings.density)),
+        Field::Seed => format!("Seed: {} (Enter for random)", settings.seed),
+        Field::Difficulty => format!("Difficulty: < {} >", settings.difficulty),
+        Field::StrictCheck => match settings.strict_check {
+            true => String::from("Check: < when pressing c >"),
+            false => String::from("Check: < after every move >"),
+        },
+        Field::Mode => format!("Mode: < {} >", settings.mode),
+        Field::CellWidth => format!("Cell width: < {} >", settings.cell_width),
+        Field::Crosshair => match settings.crosshair {
+            true => String::from("Crosshair: < on >"),
+            false => String::from("Crosshair: < off >"),
+        },
+    }
+}
+
 impl Default for SettingsState {
     fn default() -> Self {
-        let five_button_id = String::from("5by5");
-        let ten_button_id = String::from("10by10");
+        let settings = GameSettings::default();
+
+        let fields = [
+            ("width", Field::Width),
+            ("height", Field::Height),
+            ("density", Field::Density),
+            ("seed", Field::Seed),
+            ("difficulty", Field::Difficulty),
+            ("strict_check", Field::StrictCheck),
+            ("mode", Field::Mode),
+            ("cell_width", Field::CellWidth),
+            ("crosshair", Field::Crosshair),
+        ];
+        let mut menu_buttons: Vec<Button> = fields
+            .iter()
+            .map(|(id, field)| Button::new(String::from(*id), field_text(*field, &settings)))
+            .collect();
+        let mut action_map: HashMap<String, ButtonAction> = fields
+            .iter()
+            .map(|(id, field)| (String::from(*id), ButtonAction::Edit(*field)))
+            .collect();
+
+        let start_button_id = String::from("start");
         let back_button_id = String::from("back");
-        let five_button = Button::new(five_button_id.clone(), String::from("5x5"));
-        let ten_button = Button::new(ten_button_id.clone(), String::from("10x10"));
-        let back_button = Button::new(back_button_id.clone(), String::from("Back"));
+        menu_buttons.push(Button::new(start_button_id.clone(), String::from("Start!")));
+        menu_buttons.push(Button::new(back_button_id.clone(), String::from("Back")));
+        action_map.insert(start_button_id, ButtonAction::StartGame);
+        action_map.insert(back_button_id, ButtonAction::Back);
 
         SettingsState {
             selected_button: 0,
-            menu_buttons: vec![five_button, ten_button, back_button],
-            action_map: HashMap::from([
-                (
-                    five_button_id,
-                    ButtonAction::StartGame(GameSettings { size: 5 }),
-                ),
-                (
-                    ten_button_id,
-                    ButtonAction::StartGame(GameSettings { size: 10 }),
-                ),
-                (back_button_id, ButtonAction::Back),
-            ]),
+            menu_buttons,
+            action_map,
+            settings,
+            error: None,
+            typing: false,
         }
     }
 }
@@ -109,21 +442,28 @@ impl EventHandler for SettingsState {
             return match key.code {
                 KeyCode::Up => self.move_prev_button(),
                 KeyCode::Down => self.move_next_button(),
+                KeyCode::Left => self.adjust_selected(false),
+                KeyCode::Right => self.adjust_selected(true),
+                KeyCode::Char(c) if c.is_ascii_digit() => {
+                    self.type_digit(c.to_digit(10).unwrap() as u8)
+                }
+                KeyCode::Backspace => self.erase_digit(),
                 KeyCode::Enter => self.select_button(),
                 _ => Ok(ScreenMessage::Noop),
             };
         }
 
+        if let Event::Mouse(mouse) = event {
+            return self.click_button(mouse);
+        }
+
         Ok(ScreenMessage::Noop)
     }
 }
 
 impl FrameRenderer for SettingsState {
     fn render_frame(&self, frame: &mut ratatui::prelude::Frame) -> color_eyre::Result<()> {
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(3); self.menu_buttons.len()])
-            .split(frame.size());
+        let layout = self.layout(frame.size());
 
         for (i, menu_button) in self.menu_buttons.iter().enumerate() {
             let mut paragraph_widget = Paragraph::new(menu_button.text.clone());
@@ -140,6 +480,111 @@ impl FrameRenderer for SettingsState {
 
             frame.render_widget(paragraph_widget, layout[i]);
         }
+
+        if let Some(error) = &self.error {
+            frame.render_widget(
+                Paragraph::new(error.clone()).red(),
+                layout[self.menu_buttons.len()],
+            );
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(state: &mut SettingsState, id: &str) {
+        state.selected_button = state
+            .menu_buttons
+            .iter()
+            .position(|button| button.id == id)
+            .unwrap() as u8;
+        state.typing = false;
+    }
+
+    fn type_digits(state: &mut SettingsState, digits: &str) {
+        for c in digits.chars() {
+            state.type_digit(c.to_digit(10).unwrap() as u8).unwrap();
+        }
+    }
+
+    #[test]
+    fn typing_replaces_the_value_the_field_had() {
+        let mut state = SettingsState::default();
+        select(&mut state, "width");
+        type_digits(&mut state, "15");
+        assert_eq!(state.settings.width, 15);
+
+        select(&mut state, "density");
+        type_digits(&mut state, "65");
+        assert_eq!(density_percent(state.settings.density), 65);
+        assert!(state.error.is_none());
+    }
+
+    #[test]
+    fn typing_carries_on_after_erasing() {
+        let mut state = SettingsState::default();
+        select(&mut state, "height");
+        type_digits(&mut state, "12");
+        state.erase_digit().unwrap();
+        type_digits(&mut state, "8");
+        assert_eq!(state.settings.height, 18);
+    }
+
+    #[test]
+    fn digits_that_dont_fit_are_refused_with_a_message() {
+        let mut state = SettingsState::default();
+        select(&mut state, "width");
+        type_digits(&mut state, "255");
+        assert!(state.error.is_none());
+
+        type_digits(&mut state, "1");
+        assert_eq!(state.settings.width, 255);
+        assert_eq!(
+            state.error.as_deref(),
+            Some("Width must be between 1 and 50.")
+        );
+    }
+
+    #[test]
+    fn big_boards_refuse_densities_they_cant_reach() {
+        let mut state = SettingsState::default();
+        state.settings.density = 0.3;
+        assert_eq!(state.validate(), Ok(()));
+
+        state.settings.width = 30;
+        assert_eq!(
+            state.validate(),
+            Err(String::from("A 30x10 board can't be sparser than 50%."))
+        );
+        state.settings.density = 0.5;
+        assert_eq!(state.validate(), Ok(()));
+    }
+
+    #[test]
+    fn difficulties_the_board_never_reaches_are_refused() {
+        let mut state = SettingsState::default();
+        state.settings.density = 0.8;
+        state.settings.difficulty = Difficulty::Easy;
+        assert_eq!(state.validate(), Ok(()));
+
+        state.settings.difficulty = Difficulty::Medium;
+        assert_eq!(
+            state.validate(),
+            Err(String::from("A 10x10 board 80% full only comes out Easy."))
+        );
+
+        state.settings.width = 20;
+        state.settings.height = 20;
+        state.settings.density = 0.3;
+        state.settings.difficulty = Difficulty::Easy;
+        assert_eq!(
+            state.validate(),
+            Err(String::from(
+                "A 20x20 board 30% full only comes out Medium to Expert."
+            ))
+        );
+    }
+}
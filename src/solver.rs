@@ -0,0 +1,857 @@
+// Line solving for nonogram clues.
+//
+// Every row and column is solved on its own against the cells already known, and the results are
+// fed back into the grid until nothing changes. A grid that ends up fully known this way can be
+// solved without guessing, which also means its solution is unique.
+//
+// Each line is worked with the cheapest technique that still tells us something, so the report
+// also says how much effort the puzzle took.
+
+/// The techniques the solver applies to a line, from cheapest to most thorough.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Technique {
+    /// Blocks that cover the same cells whether the clue is packed to the left or to the right of
+    /// an otherwise empty line.
+    Overlap,
+    /// The same overlap, but with the known cells pushing the packings away from the edges.
+    EdgeForcing,
+    /// Stretches between crosses that are too short to hold any block of the clue.
+    GapAnalysis,
+    /// Every arrangement of the clue that fits the line, checked one by one.
+    Exhaustive,
+}
+
+impl std::fmt::Display for Technique {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Technique::Overlap => "overlap",
+            Technique::EdgeForcing => "edge forcing",
+            Technique::GapAnalysis => "gap analysis",
+            Technique::Exhaustive => "exhaustive line search",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const TECHNIQUES: [Technique; 4] = [
+    Technique::Overlap,
+    Technique::EdgeForcing,
+    Technique::GapAnalysis,
+    Technique::Exhaustive,
+];
+
+/// A single cell worked out by the solver.
+#[derive(Clone, Copy, Debug)]
+// The game only rates boards by technique so far, but the report says which cells were deduced
+// for anything else built on the solver.
+#[allow(dead_code)]
+pub struct Deduction {
+    pub row: usize,
+    pub column: usize,
+    pub value: bool,
+    pub technique: Technique,
+}
+
+pub struct LineSolveReport {
+    // The grid as far as line logic got.
+    pub grid: Vec<Vec<Option<bool>>>,
+    // Every cell the solver filled in, in the order it found them.
+    pub deductions: Vec<Deduction>,
+    // Number of sweeps over all rows and columns that found something new.
+    pub passes: u32,
+    // Set when the clues can't be satisfied; the grid is then only partially meaningful.
+    pub contradiction: bool,
+}
+
+impl LineSolveReport {
+    /// True if line logic alone worked out every cell.
+    pub fn is_solved(&self) -> bool {
+        !self.contradiction && self.grid.iter().flatten().all(|c| c.is_some())
+    }
+
+    /// The most expensive technique the solver needed, if it found anything at all.
+    pub fn hardest_technique(&self) -> Option<Technique> {
+        self.deductions.iter().map(|d| d.technique).max()
+    }
+}
+
+/// Line solves the puzzle described by the clues, starting from an empty grid.
+pub fn line_solve(row_counts: &[Vec<u8>], column_counts: &[Vec<u8>]) -> LineSolveReport {
+    let grid = vec![vec![None; column_counts.len()]; row_counts.len()];
+    line_solve_from(row_counts, column_counts, grid)
+}
+
+/// Line solves the puzzle described by the clues, starting from cells that are already known.
+/// Repeatedly sweeps over every row and column until a sweep finds nothing new.
+pub fn line_solve_from(
+    row_counts: &[Vec<u8>],
+    column_counts: &[Vec<u8>],
+    mut grid: Vec<Vec<Option<bool>>>,
+) -> LineSolveReport {
+    let mut deductions: Vec<Deduction> = Vec::new();
+    let mut passes = 0;
+
+    loop {
+        let found_before = deductions.len();
+
+        for (m, clue) in row_counts.iter().enumerate() {
+            let line = grid[m].clone();
+            let Some(solved) = solve_line_cheapest(clue, line, |n, value, technique| {
+                deductions.push(Deduction {
+                    row: m,
+                    column: n,
+                    value,
+                    technique,
+                })
+            }) else {
+                return LineSolveReport {
+                    grid,
+                    deductions,
+                    passes: passes + 1,
+                    contradiction: true,
+                };
+            };
+            grid[m] = solved;
+        }
+
+        for (n, clue) in column_counts.iter().enumerate() {
+            let line: Vec<Option<bool>> = grid.iter().map(|row| row[n]).collect();
+            let Some(solved) = solve_line_cheapest(clue, line, |m, value, technique| {
+                deductions.push(Deduction {
+                    row: m,
+                    column: n,
+                    value,
+                    technique,
+                })
+            }) else {
+                return LineSolveReport {
+                    grid,
+                    deductions,
+                    passes: passes + 1,
+                    contradiction: true,
+                };
+            };
+            for (row, value) in grid.iter_mut().zip(solved) {
+                row[n] = value;
+            }
+        }
+
+        if deductions.len() == found_before {
+            break;
+        }
+        passes += 1;
+    }
+
+    LineSolveReport {
+        grid,
+        deductions,
+        passes,
+        contradiction: false,
+    }
+}
+
+/// Whether a set of clues pins down a single grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Uniqueness {
+    Contradictory,
+    Unique,
+    Multiple,
+}
+
+pub struct SearchReport {
+    // The grids found that match the clues, never more than the cap the search was given.
+    pub solutions: Vec<Vec<Vec<bool>>>,
+}
+
+impl SearchReport {
+    /// Only tells `Unique` and `Multiple` apart if the search was allowed to find at least two
+    /// solutions.
+    pub fn uniqueness(&self) -> Uniqueness {
+        match self.solutions.len() {
+            0 => Uniqueness::Contradictory,
+            1 => Uniqueness::Unique,
+            _ => Uniqueness::Multiple,
+        }
+    }
+}
+
+/// Counts the grids that match the clues and agree with the cells already known, stopping once
+/// `cap` of them have been found. Line logic does as much as it can, and when it stalls the search
+/// guesses a cell both ways and carries on from each.
+pub fn count_solutions(
+    row_counts: &[Vec<u8>],
+    column_counts: &[Vec<u8>],
+    grid: Vec<Vec<Option<bool>>>,
+    cap: usize,
+) -> SearchReport {
+    let mut report = SearchReport {
+        solutions: Vec::new(),
+    };
+    search(row_counts, column_counts, grid, cap, &mut report);
+    report
+}
+
+fn search(
+    row_counts: &[Vec<u8>],
+    column_counts: &[Vec<u8>],
+    grid: Vec<Vec<Option<bool>>>,
+    cap: usize,
+    report: &mut SearchReport,
+) {
+    if report.solutions.len() >= cap {
+        return;
+    }
+
+    let solved = line_solve_from(row_counts, column_counts, grid);
+    if solved.contradiction {
+        return;
+    }
+
+    let undecided = solved
+        .grid
+        .iter()
+        .enumerate()
+        .find_map(|(m, row)| row.iter().position(|c| c.is_none()).map(|n| (m, n)));
+
+    match undecided {
+        None => report.solutions.push(
+            solved
+                .grid
+                .iter()
+                .map(|row| row.iter().map(|c| c.unwrap()).collect())
+                .collect(),
+        ),
+        Some((m, n)) => {
+            for value in [true, false] {
+                let mut guess = solved.grid.clone();
+                guess[m][n] = Some(value);
+                search(row_counts, column_counts, guess, cap, report);
+            }
+        }
+    }
+}
+
+/// Works a line until no technique finds anything more, always reaching for the cheapest
+/// technique that still makes progress. Every newly known cell is passed to `found`. Returns
+/// `None` if the line contradicts its clue.
+fn solve_line_cheapest(
+    clue: &[u8],
+    mut line: Vec<Option<bool>>,
+    mut found: impl FnMut(usize, bool, Technique),
+) -> Option<Vec<Option<bool>>> {
+    'progress: loop {
+        for technique in TECHNIQUES {
+            let deduced = apply_technique(technique, clue, &line)?;
+
+            let mut progressed = false;
+            for (i, (known, new)) in line.iter_mut().zip(deduced).enumerate() {
+                match (*known, new) {
+                    (None, Some(value)) => {
+                        *known = Some(value);
+                        found(i, value, technique);
+                        progressed = true;
+                    }
+                    (Some(a), Some(b)) if a != b => return None,
+                    _ => {}
+                }
+            }
+
+            if progressed {
+                continue 'progress;
+            }
+        }
+
+        return Some(line);
+    }
+}
+
+fn apply_technique(
+    technique: Technique,
+    clue: &[u8],
+    line: &[Option<bool>],
+) -> Option<Vec<Option<bool>>> {
+    match technique {
+        Technique::Overlap => overlap(clue, line.len()),
+        Technique::EdgeForcing => edge_forcing(clue, line),
+        Technique::GapAnalysis => Some(gap_analysis(clue, line)),
+        Technique::Exhaustive => solve_line(clue, line),
+    }
+}
+
+/// Cells that every packing of the clue fills or leaves empty, from the clue and the line length
+/// alone.
+fn overlap(clue: &[u8], n: usize) -> Option<Vec<Option<bool>>> {
+    let total = clue.iter().map(|c| *c as usize).sum::<usize>() + clue.len().saturating_sub(1);
+    if total > n {
+        return None;
+    }
+
+    let mut left_starts = Vec::with_capacity(clue.len());
+    let mut start = 0;
+    for len in clue {
+        left_starts.push(start);
+        start += *len as usize + 1;
+    }
+    let right_starts: Vec<usize> = left_starts.iter().map(|s| s + n - total).collect();
+
+    Some(overlap_from_packings(clue, n, &left_starts, &right_starts))
+}
+
+/// Like `overlap`, but packs the clue as far left and as far right as the known cells allow.
+fn edge_forcing(clue: &[u8], line: &[Option<bool>]) -> Option<Vec<Option<bool>>> {
+    let n = line.len();
+    let left_starts = leftmost_starts(clue, line)?;
+
+    let reversed_clue: Vec<u8> = clue.iter().rev().copied().collect();
+    let reversed_line: Vec<Option<bool>> = line.iter().rev().copied().collect();
+    let right_starts: Vec<usize> = leftmost_starts(&reversed_clue, &reversed_line)?
+        .iter()
+        .rev()
+        .zip(clue)
+        .map(|(start, len)| n - start - *len as usize)
+        .collect();
+
+    Some(overlap_from_packings(clue, n, &left_starts, &right_starts))
+}
+
+/// Given where each block starts in the leftmost and rightmost packings, fills the cells each
+/// block covers in both, and crosses the cells no block can reach.
+fn overlap_from_packings(
+    clue: &[u8],
+    n: usize,
+    left_starts: &[usize],
+    right_starts: &[usize],
+) -> Vec<Option<bool>> {
+    let mut deduced = vec![Some(false); n];
+    for (j, len) in clue.iter().enumerate() {
+        let reach = left_starts[j]..right_starts[j] + *len as usize;
+        deduced[reach].iter_mut().for_each(|c| *c = None);
+    }
+    for (j, len) in clue.iter().enumerate() {
+        let covered = right_starts[j]..left_starts[j] + *len as usize;
+        if !covered.is_empty() {
+            deduced[covered].iter_mut().for_each(|c| *c = Some(true));
+        }
+    }
+    deduced
+}
+
+/// Crosses every stretch between crosses (or the edges) that is shorter than the smallest block.
+fn gap_analysis(clue: &[u8], line: &[Option<bool>]) -> Vec<Option<bool>> {
+    let mut deduced = vec![None; line.len()];
+    let smallest = clue.iter().min().map_or(usize::MAX, |c| *c as usize);
+
+    let mut start = 0;
+    for i in 0..=line.len() {
+        if i == line.len() || line[i] == Some(false) {
+            if i > start && i - start < smallest {
+                deduced[start..i].iter_mut().for_each(|c| *c = Some(false));
+            }
+            start = i + 1;
+        }
+    }
+    deduced
+}
+
+/// `fits[i][j]` is true when cells `i..` of the line can hold blocks `j..` of the clue.
+fn fits_table(clue: &[u8], line: &[Option<bool>]) -> Vec<Vec<bool>> {
+    let n = line.len();
+    let k = clue.len();
+
+    let mut fits = vec![vec![false; k + 1]; n + 1];
+    fits[n][k] = true;
+    for i in (0..n).rev() {
+        for j in 0..=k {
+            fits[i][j] = (can_be_empty(line, i) && fits[i + 1][j])
+                || (j < k && block_fits(clue, line, &fits, i, j));
+        }
+    }
+    fits
+}
+
+fn can_be_empty(line: &[Option<bool>], i: usize) -> bool {
+    line[i] != Some(true)
+}
+
+/// True if block `j` of the clue can start at `start` with the rest of the clue fitting after it.
+fn block_fits(
+    clue: &[u8],
+    line: &[Option<bool>],
+    fits: &[Vec<bool>],
+    start: usize,
+    j: usize,
+) -> bool {
+    let n = line.len();
+    let end = start + clue[j] as usize;
+    if end > n || line[start..end].contains(&Some(false)) {
+        return false;
+    }
+
+    if end == n {
+        fits[n][j + 1]
+    } else {
+        can_be_empty(line, end) && fits[end + 1][j + 1]
+    }
+}
+
+/// Where each block starts when the clue is packed as far left as the known cells allow.
+fn leftmost_starts(clue: &[u8], line: &[Option<bool>]) -> Option<Vec<usize>> {
+    let fits = fits_table(clue, line);
+    if !fits[0][0] {
+        return None;
+    }
+
+    let mut starts = Vec::with_capacity(clue.len());
+    let mut i = 0;
+    while starts.len() < clue.len() {
+        let j = starts.len();
+        if block_fits(clue, line, &fits, i, j) {
+            starts.push(i);
+            i += clue[j] as usize + 1;
+        } else {
+            i += 1;
+        }
+    }
+    Some(starts)
+}
+
+/// Works out which cells of a single line can still be filled and which can still be empty,
+/// given its clue and the cells that are already known. Returns `None` when no arrangement of the
+/// clue fits the known cells.
+pub fn solve_line(clue: &[u8], line: &[Option<bool>]) -> Option<Vec<Option<bool>>> {
+    let n = line.len();
+    let k = clue.len();
+
+    let fits = fits_table(clue, line);
+    if !fits[0][0] {
+        return None;
+    }
+
+    // Walk every arrangement that fits and note what each cell can be.
+    let mut reachable = vec![vec![false; k + 1]; n + 1];
+    let mut seen_filled = vec![false; n];
+    let mut seen_empty = vec![false; n];
+    reachable[0][0] = true;
+    for i in 0..n {
+        for j in 0..=k {
+            if !reachable[i][j] || !fits[i][j] {
+                continue;
+            }
+
+            if can_be_empty(line, i) && fits[i + 1][j] {
+                seen_empty[i] = true;
+                reachable[i + 1][j] = true;
+            }
+
+            if j < k && block_fits(clue, line, &fits, i, j) {
+                let end = i + clue[j] as usize;
+                seen_filled[i..end].iter_mut().for_each(|c| *c = true);
+                if end < n {
+                    seen_empty[end] = true;
+                }
+                reachable[(end + 1).min(n)][j + 1] = true;
+            }
+        }
+    }
+
+    Some(
+        seen_filled
+            .iter()
+            .zip(seen_empty.iter())
+            .map(|(filled, empty)| match (filled, empty) {
+                (true, false) => Some(true),
+                (false, true) => Some(false),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Which blocks of the clue are already matched by finished runs of filled cells. Runs are
+/// matched to blocks in from either end of the line, up to the first unknown cell. Once the
+/// filled runs are exactly the clue, every block is matched.
+pub fn satisfied_blocks(clue: &[u8], line: &[Option<bool>]) -> Vec<bool> {
+    let filled = closed_runs(line.iter().map(|cell| Some(*cell == Some(true))));
+    if filled == clue {
+        return vec![true; clue.len()];
+    }
+
+    let mut satisfied = vec![false; clue.len()];
+    for (j, (run, block)) in closed_runs(line.iter().copied())
+        .into_iter()
+        .zip(clue)
+        .enumerate()
+    {
+        if run != *block {
+            break;
+        }
+        satisfied[j] = true;
+    }
+    for (j, (run, block)) in closed_runs(line.iter().rev().copied())
+        .into_iter()
+        .zip(clue.iter().rev())
+        .enumerate()
+    {
+        if run != *block {
+            break;
+        }
+        satisfied[clue.len() - 1 - j] = true;
+    }
+    satisfied
+}
+
+/// Lengths of the runs of filled cells that are crossed or at the edge on both sides, from the
+/// start of the line up to the first unknown cell.
+fn closed_runs(line: impl Iterator<Item = Option<bool>>) -> Vec<u8> {
+    let mut runs = Vec::new();
+    let mut run = 0;
+    for cell in line {
+        match cell {
+            Some(true) => run += 1,
+            Some(false) => {
+                if run > 0 {
+                    runs.push(run);
+                }
+                run = 0;
+            }
+            None => return runs,
+        }
+    }
+    if run > 0 {
+        runs.push(run);
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    fn clue_of(line: &[bool]) -> Vec<u8> {
+        line.split(|filled| !filled)
+            .filter(|run| !run.is_empty())
+            .map(|run| run.len() as u8)
+            .collect()
+    }
+
+    /// What every line matching the clue and the known cells agrees on, found by trying every
+    /// line of that length.
+    fn brute_force_line(clue: &[u8], known: &[Option<bool>]) -> Option<Vec<Option<bool>>> {
+        let n = known.len();
+        let mut agreed: Option<Vec<Option<bool>>> = None;
+        for bits in 0..1u32 << n {
+            let line: Vec<bool> = (0..n).map(|i| bits & (1 << i) != 0).collect();
+            let fits_known = known
+                .iter()
+                .zip(&line)
+                .all(|(k, v)| k.is_none_or(|k| k == *v));
+            if !fits_known || clue_of(&line) != clue {
+                continue;
+            }
+            agreed = Some(match agreed {
+                None => line.iter().map(|v| Some(*v)).collect(),
+                Some(agreed) => agreed
+                    .iter()
+                    .zip(&line)
+                    .map(|(a, v)| a.filter(|a| a == v))
+                    .collect(),
+            });
+        }
+        agreed
+    }
+
+    /// A random line with some of its cells revealed, and its clue.
+    fn random_line(rng: &mut ChaCha8Rng) -> (Vec<u8>, Vec<Option<bool>>) {
+        let n = rng.gen_range(1..=12);
+        let density = rng.gen_range(0.2..0.8);
+        let line: Vec<bool> = (0..n).map(|_| rng.gen_bool(density)).collect();
+        let reveal = rng.gen_range(0.0..0.6);
+        let known = line
+            .iter()
+            .map(|v| rng.gen_bool(reveal).then_some(*v))
+            .collect();
+        (clue_of(&line), known)
+    }
+
+    /// True if every cell the technique decided is one that every matching line agrees on.
+    fn is_sound(deduced: &[Option<bool>], truth: &[Option<bool>]) -> bool {
+        deduced
+            .iter()
+            .zip(truth)
+            .all(|(d, t)| d.is_none() || d == t)
+    }
+
+    fn random_grid(rng: &mut ChaCha8Rng, width: usize, height: usize) -> Vec<Vec<bool>> {
+        (0..height)
+            .map(|_| (0..width).map(|_| rng.gen_bool(0.5)).collect())
+            .collect()
+    }
+
+    fn clues_of(grid: &[Vec<bool>]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        let rows = grid.iter().map(|row| clue_of(row)).collect();
+        let columns = (0..grid[0].len())
+            .map(|n| clue_of(&grid.iter().map(|row| row[n]).collect::<Vec<_>>()))
+            .collect();
+        (rows, columns)
+    }
+
+    // A heart, a 5x4 board line logic solves on its own.
+    fn heart() -> (Vec<Vec<u8>>, Vec<Vec<u8>>) {
+        (
+            vec![vec![1, 1], vec![5], vec![3], vec![1]],
+            vec![vec![1], vec![3], vec![3], vec![3], vec![1]],
+        )
+    }
+
+    #[test]
+    fn solve_line_matches_brute_force() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let (clue, known) = random_line(&mut rng);
+            assert_eq!(
+                solve_line(&clue, &known),
+                brute_force_line(&clue, &known),
+                "clue {:?}, line {:?}",
+                clue,
+                known
+            );
+        }
+    }
+
+    #[test]
+    fn techniques_only_deduce_what_every_solution_agrees_on() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        for _ in 0..1000 {
+            let (clue, known) = random_line(&mut rng);
+            let truth = brute_force_line(&clue, &known).unwrap();
+            let empty = brute_force_line(&clue, &vec![None; known.len()]).unwrap();
+
+            let overlap = overlap(&clue, known.len()).unwrap();
+            assert!(is_sound(&overlap, &empty), "overlap on {:?}", clue);
+            let edge_forcing = edge_forcing(&clue, &known).unwrap();
+            assert!(
+                is_sound(&edge_forcing, &truth),
+                "edge forcing on {:?} {:?}",
+                clue,
+                known
+            );
+            let gaps = gap_analysis(&clue, &known);
+            assert!(
+                is_sound(&gaps, &truth),
+                "gap analysis on {:?} {:?}",
+                clue,
+                known
+            );
+        }
+    }
+
+    #[test]
+    fn techniques_on_known_lines() {
+        assert_eq!(
+            overlap(&[3, 1], 6),
+            Some(vec![None, Some(true), Some(true), None, None, None])
+        );
+        assert_eq!(overlap(&[3, 3], 6), None);
+        assert_eq!(overlap(&[], 3), Some(vec![Some(false); 3]));
+
+        // A filled cell at the edge pins the first block to it.
+        let line = [Some(true), None, None, None, None];
+        assert_eq!(
+            edge_forcing(&[2], &line),
+            Some(vec![
+                Some(true),
+                Some(true),
+                Some(false),
+                Some(false),
+                Some(false)
+            ])
+        );
+
+        // The single cell between the crosses is too short for a block of 2.
+        let line = [None, Some(false), None, Some(false), None, None];
+        assert_eq!(
+            gap_analysis(&[2], &line),
+            vec![Some(false), None, Some(false), None, None, None]
+        );
+    }
+
+    #[test]
+    fn solve_line_rejects_contradictions() {
+        assert_eq!(
+            solve_line(&[2], &[Some(true), Some(false), Some(true)]),
+            None
+        );
+        assert_eq!(solve_line(&[4], &[None; 3]), None);
+        assert_eq!(solve_line(&[], &[Some(true)]), None);
+    }
+
+    #[test]
+    fn line_solve_reports_a_solved_board() {
+        let (rows, columns) = heart();
+        let report = line_solve(&rows, &columns);
+
+        assert!(report.is_solved());
+        assert!(report.passes > 0);
+        assert_eq!(report.deductions.len(), 20);
+        for deduction in &report.deductions {
+            assert_eq!(
+                report.grid[deduction.row][deduction.column],
+                Some(deduction.value)
+            );
+        }
+    }
+
+    #[test]
+    fn line_solve_agrees_with_the_grid_the_clues_came_from() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for _ in 0..200 {
+            let grid = random_grid(&mut rng, 6, 6);
+            let (rows, columns) = clues_of(&grid);
+            let report = line_solve(&rows, &columns);
+
+            assert!(!report.contradiction);
+            for (known, solution) in report.grid.iter().flatten().zip(grid.iter().flatten()) {
+                assert!(known.is_none_or(|k| k == *solution));
+            }
+        }
+    }
+
+    #[test]
+    fn line_solve_stalls_on_ambiguous_clues() {
+        let report = line_solve(&[vec![1], vec![1]], &[vec![1], vec![1]]);
+        assert!(!report.is_solved());
+        assert!(!report.contradiction);
+        assert!(report.deductions.is_empty());
+    }
+
+    #[test]
+    fn line_solve_finds_contradictions() {
+        let report = line_solve(&[vec![2], vec![2]], &[vec![1], vec![1]]);
+        assert!(report.contradiction);
+        assert!(!report.is_solved());
+    }
+
+    /// Every grid of the given size that matches the clues.
+    fn brute_force_grids(
+        rows: &[Vec<u8>],
+        columns: &[Vec<u8>],
+        width: usize,
+        height: usize,
+    ) -> Vec<Vec<Vec<bool>>> {
+        (0..1u32 << (width * height))
+            .map(|bits| {
+                (0..height)
+                    .map(|m| {
+                        (0..width)
+                            .map(|n| bits & (1 << (m * width + n)) != 0)
+                            .collect()
+                    })
+                    .collect::<Vec<Vec<bool>>>()
+            })
+            .filter(|grid| clues_of(grid) == (rows.to_vec(), columns.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn count_solutions_tells_clue_sets_apart() {
+        let (rows, columns) = heart();
+        let report = count_solutions(&rows, &columns, vec![vec![None; 5]; 4], 2);
+        assert_eq!(report.uniqueness(), Uniqueness::Unique);
+        assert_eq!(clues_of(&report.solutions[0]), heart());
+
+        let ambiguous = [vec![1], vec![1]];
+        let report = count_solutions(&ambiguous, &ambiguous, vec![vec![None; 2]; 2], 2);
+        assert_eq!(report.uniqueness(), Uniqueness::Multiple);
+
+        let report = count_solutions(&[vec![2], vec![2]], &ambiguous, vec![vec![None; 2]; 2], 2);
+        assert_eq!(report.uniqueness(), Uniqueness::Contradictory);
+    }
+
+    #[test]
+    fn count_solutions_stops_at_the_cap() {
+        // Every permutation matrix fits, so a 4x4 board has 24 solutions.
+        let ones = vec![vec![1]; 4];
+        let unknown = vec![vec![None; 4]; 4];
+        assert_eq!(
+            count_solutions(&ones, &ones, unknown.clone(), 5)
+                .solutions
+                .len(),
+            5
+        );
+        assert_eq!(
+            count_solutions(&ones, &ones, unknown.clone(), 1)
+                .solutions
+                .len(),
+            1
+        );
+        assert_eq!(
+            count_solutions(&ones, &ones, unknown, 100).solutions.len(),
+            24
+        );
+    }
+
+    #[test]
+    fn count_solutions_respects_known_cells() {
+        let ambiguous = [vec![1], vec![1]];
+        let grid = vec![vec![Some(true), None], vec![None, None]];
+        let report = count_solutions(&ambiguous, &ambiguous, grid, 2);
+        assert_eq!(report.uniqueness(), Uniqueness::Unique);
+        assert_eq!(
+            report.solutions[0],
+            vec![vec![true, false], vec![false, true]]
+        );
+    }
+
+    #[test]
+    fn count_solutions_matches_brute_force() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        for _ in 0..30 {
+            let grid = random_grid(&mut rng, 4, 3);
+            let (rows, columns) = clues_of(&grid);
+
+            let mut expected = brute_force_grids(&rows, &columns, 4, 3);
+            let mut found = count_solutions(&rows, &columns, vec![vec![None; 4]; 3], 100).solutions;
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    fn line(cells: &str) -> Vec<Option<bool>> {
+        cells
+            .chars()
+            .map(|c| match c {
+                '#' => Some(true),
+                'X' => Some(false),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn satisfied_blocks_match_finished_runs_from_either_end() {
+        assert_eq!(satisfied_blocks(&[2, 1], &line("##X..")), [true, false]);
+        assert_eq!(satisfied_blocks(&[2, 1], &line("...X#")), [false, true]);
+        assert_eq!(
+            satisfied_blocks(&[1, 1, 1], &line("#X...X#")),
+            [true, false, true]
+        );
+    }
+
+    #[test]
+    fn satisfied_blocks_need_runs_closed_on_both_sides() {
+        // The run could still grow into the unknown cell.
+        assert_eq!(satisfied_blocks(&[2, 1], &line("##...")), [false, false]);
+        // Matching stops at the first run that isn't its block.
+        assert_eq!(satisfied_blocks(&[2, 2], &line("#X##X.")), [false, false]);
+    }
+
+    #[test]
+    fn satisfied_blocks_are_all_matched_once_the_runs_are_the_clue() {
+        // Unknown cells count as empty here, since the filled runs already make up the clue.
+        assert_eq!(satisfied_blocks(&[2, 1], &line(".##.#.")), [true, true]);
+        assert_eq!(satisfied_blocks(&[], &line("X..")), Vec::<bool>::new());
+    }
+}
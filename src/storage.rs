@@ -0,0 +1,66 @@
+// Files kept between runs, such as saved games.
+//
+// Everything lives in a "no2" directory under the user's data directory: `$XDG_DATA_HOME` or
+// `~/.local/share` on Linux, `~/Library/Application Support` on macOS and `%APPDATA%` on Windows.
+
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+/// Where a file with the given name is kept.
+pub fn path(name: &str) -> io::Result<PathBuf> {
+    Ok(data_dir()?.join(name))
+}
+
+fn data_dir() -> io::Result<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|h| h.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|h| h.join(".local").join("share")))
+    };
+
+    base.map(|b| b.join("no2")).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::NotFound,
+            "couldn't find the user's data directory",
+        )
+    })
+}
+
+/// Writes a file in the data directory. The old contents are only replaced once the new ones have
+/// been written in full.
+pub fn write(name: &str, contents: &str) -> io::Result<()> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    let temporary = dir.join(format!("{}.tmp", name));
+    fs::write(&temporary, contents)?;
+    fs::rename(temporary, dir.join(name))
+}
+
+/// Reads a file in the data directory, or `None` if there isn't one.
+pub fn read(name: &str) -> io::Result<Option<String>> {
+    match fs::read_to_string(data_dir()?.join(name)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+pub fn exists(name: &str) -> bool {
+    data_dir().is_ok_and(|dir| dir.join(name).is_file())
+}
+
+pub fn remove(name: &str) -> io::Result<()> {
+    match fs::remove_file(data_dir()?.join(name)) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
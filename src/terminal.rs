@@ -1,17 +1,27 @@
-use crossterm::{execute, terminal::*};
+use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::*,
+};
 use ratatui::prelude::*;
 use std::io::{self, stdout, Stdout};
 
 pub type TerminalInterface = Terminal<CrosstermBackend<Stdout>>;
 
 pub fn init_terminal() -> io::Result<TerminalInterface> {
-    execute!(stdout(), EnterAlternateScreen)?;
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture)?;
     enable_raw_mode()?;
     Terminal::new(CrosstermBackend::new(stdout()))
 }
 
 pub fn restore_terminal() -> io::Result<()> {
-    execute!(stdout(), LeaveAlternateScreen)?;
+    execute!(stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
 }
+
+/// The area screens are drawn in, so mouse events can be mapped back to what was drawn there.
+pub fn screen_area() -> io::Result<Rect> {
+    let (width, height) = size()?;
+    Ok(Rect::new(0, 0, width, height))
+}
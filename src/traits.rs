@@ -1,23 +1,27 @@
 use crossterm::event::Event;
 use ratatui::Frame;
-use std::io;
 
 use crate::app::Screen;
+use crate::screens::game::GameSettings;
 
+// Messages only live long enough to be handled, so carrying a whole screen in one is fine.
+#[allow(clippy::large_enum_variant)]
 pub enum ScreenMessage {
     Exit,
     ChangeScreen(Screen),
+    // Generating a board can take a moment, so the app says so before starting the game.
+    StartGame(GameSettings),
     Noop,
 }
 
 pub trait FrameRenderer {
-    fn render_frame(&self, frame: &mut Frame) -> color_eyre::Result<()> {
+    fn render_frame(&self, _frame: &mut Frame) -> color_eyre::Result<()> {
         todo!("render_frame not yet implemented!");
     }
 }
 
 pub trait EventHandler {
-    fn handle_events(&mut self, event: Event) -> color_eyre::Result<ScreenMessage> {
+    fn handle_events(&mut self, _event: Event) -> color_eyre::Result<ScreenMessage> {
         todo!("handle_events not yet implemented!");
     }
 }
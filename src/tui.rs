@@ -1,6 +0,0 @@
-use crate::app;
-use ratatui::Frame;
-
-pub fn render_tui(frame: &mut Frame, app_state: &app::GlobalState) {
-    todo!()
-}